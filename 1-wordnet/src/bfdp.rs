@@ -1,50 +1,5 @@
-use digraph::Digraph;
-use std::collections::VecDeque;
-
-// breadth first search over digraphs
-// for a slower version which supports returning the path rather than just the distance, see tag 1-wordnet-1.0
-pub struct BfsResult {
-    marked: Vec<bool>,
-    dist_to: Vec<i32>,
-}
-
-pub fn search(g: &Digraph, sources: Vec<usize>) -> BfsResult {
-    let num_vertices = g.vertices();
-    let mut result = BfsResult {
-        marked: vec![false; num_vertices],
-        dist_to: vec![0; num_vertices],
-    };
-
-    let mut q = VecDeque::new();
-    for v in sources {
-        result.marked[v] = true;
-        result.dist_to[v] = 0;
-        q.push_back(v);
-    }
-    while q.len() > 0 {
-        let v = q.pop_front().expect("queue length known to be > 0");
-        for w in g.adj(v) {
-            if !result.marked[*w] {
-                result.dist_to[*w] = result.dist_to[v] + 1;
-                result.marked[*w] = true;
-                q.push_back(*w);
-            }
-        }
-    }
-
-    result
-}
-
-impl BfsResult {
-    /// Returns None if there is no path
-    pub fn dist_to(&self, v: usize) -> Option<i32> {
-        if self.marked[v] {
-            Some(self.dist_to[v])
-        } else {
-            None
-        }
-    }
-}
+// breadth first search over digraphs; the implementation lives in the graph crate so other assignments can share it
+pub use graph::bfs::{search, BfsResult};
 
 #[cfg(test)]
 mod tests {
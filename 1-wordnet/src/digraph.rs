@@ -1,3 +1,4 @@
+use graph::{Graph, Neighbour};
 use std::slice::Iter;
 
 pub struct Digraph {
@@ -6,6 +7,20 @@ pub struct Digraph {
     adj: Vec<Vec<usize>>,
 }
 
+impl Graph for Digraph {
+    fn vertices(&self) -> usize {
+        self.v
+    }
+
+    /// Every edge has a weight of 1.
+    fn for_each_neighbour<F: FnMut(Neighbour)>(&self, vertex: usize, mut f: F) {
+        let checked_index = self.checked_vertex(vertex);
+        for (i, &w) in self.adj[checked_index].iter().enumerate() {
+            f(Neighbour { vertex: w, edge: i, weight: 1.0 });
+        }
+    }
+}
+
 impl Digraph {
     pub fn new(v: usize) -> Digraph {
         Digraph {
@@ -15,15 +30,6 @@ impl Digraph {
         }
     }
 
-    pub fn vertices(&self) -> usize {
-        self.v
-    }
-
-    fn checked_vertex(&self, vertex: usize) -> usize {
-        assert!(vertex < self.v, format!("Vertex {} must be < num vertexes ({})", vertex, self.v));
-        vertex
-    }
-
     pub fn add_edge(&mut self, v: usize, w: usize) {
         let from_index = self.checked_vertex(v);
         let checked_index = self.checked_vertex(w);
@@ -35,6 +41,26 @@ impl Digraph {
         let checked_index = self.checked_vertex(vertex);
         self.adj[checked_index].iter()
     }
+
+    pub fn edges(&self) -> usize {
+        self.e as usize
+    }
+
+    pub fn outdegree(&self, vertex: usize) -> usize {
+        let checked_index = self.checked_vertex(vertex);
+        self.adj[checked_index].len()
+    }
+
+    /// Returns a copy of this digraph with all edges reversed.
+    pub fn reverse(&self) -> Digraph {
+        let mut reversed = Digraph::new(self.v);
+        for v in 0..self.v {
+            for &w in self.adj[v].iter() {
+                reversed.add_edge(w, v);
+            }
+        }
+        reversed
+    }
 }
 
 #[cfg(test)]
@@ -58,4 +84,18 @@ mod tests {
 
         assert_eq!(g.adj(1).collect::<Vec<_>>().len(), 0);
     }
+
+    #[test]
+    fn reversing_should_flip_every_edge() {
+        let mut g = Digraph::new(3);
+        g.add_edge(0, 1);
+        g.add_edge(0, 2);
+
+        let r = g.reverse();
+
+        assert_eq!(r.edges(), 2);
+        assert_eq!(r.outdegree(0), 0);
+        assert_eq!(r.adj(1).cloned().collect::<Vec<_>>(), vec![0]);
+        assert_eq!(r.adj(2).cloned().collect::<Vec<_>>(), vec![0]);
+    }
 }
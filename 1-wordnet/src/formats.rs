@@ -0,0 +1,369 @@
+use rustc_serialize::json::Json;
+use std::borrow::ToOwned;
+use std::collections::HashMap;
+use std::io;
+use std::io::prelude::*;
+use wordnet::{invalid_data, Progress, Synset, WordNet};
+
+/// The on-disk layouts that a lexical graph can be loaded from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// `synsets.txt` + `hypernyms.txt` as provided with the Coursera assignment.
+    Coursera,
+    /// `synsets.csv` (with `id` and `nouns` columns) + `hypernyms.csv` (with `synset` and `hypernym` columns), each
+    /// starting with a header row.
+    Csv,
+    /// `data.noun` + `index.noun` from a Princeton WordNet database distribution.
+    Princeton,
+    /// A single JSON taxonomy file; see `parse_json` for the layout.
+    Json,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "coursera" => Some(Format::Coursera),
+            "csv" => Some(Format::Csv),
+            "princeton" => Some(Format::Princeton),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+
+    /// How many input files are needed to describe a graph in this format.
+    pub fn file_count(&self) -> usize {
+        match *self {
+            Format::Coursera | Format::Csv | Format::Princeton => 2,
+            Format::Json => 1,
+        }
+    }
+}
+
+/// Parses a WordNet from the given paths, which must contain exactly `format.file_count()` entries. Only the Coursera
+/// format currently reports to the `progress` callback.
+pub fn parse_files(format: Format, paths: &[String], progress: Option<&mut FnMut(Progress)>) -> io::Result<WordNet> {
+    use std::fs::File;
+    use std::io::BufReader;
+
+    assert_eq!(paths.len(), format.file_count());
+
+    let (synsets, hypernyms_edges) = match format {
+        Format::Coursera => return WordNet::create_from_readers(
+            BufReader::new(try!(File::open(&paths[0]))),
+            BufReader::new(try!(File::open(&paths[1]))),
+            progress),
+        Format::Csv => try!(parse_csv(
+            BufReader::new(try!(File::open(&paths[0]))),
+            BufReader::new(try!(File::open(&paths[1]))))),
+        Format::Princeton => try!(parse_princeton(
+            BufReader::new(try!(File::open(&paths[0]))),
+            BufReader::new(try!(File::open(&paths[1]))))),
+        Format::Json => try!(parse_json(BufReader::new(try!(File::open(&paths[0]))))),
+    };
+
+    Ok(WordNet::create_from_synsets_and_hypernyms(synsets, hypernyms_edges))
+}
+
+/// Hands out dense synset ids (as needed by the digraph) for the arbitrary ids used by the input files.
+struct IdAllocator {
+    ids: HashMap<String, usize>,
+}
+
+impl IdAllocator {
+    fn new() -> IdAllocator {
+        IdAllocator { ids: HashMap::new() }
+    }
+
+    /// Returns the new dense id, or None if `external_id` was already allocated.
+    fn allocate(&mut self, external_id: &str) -> Option<usize> {
+        if self.ids.contains_key(external_id) {
+            return None;
+        }
+        let id = self.ids.len();
+        self.ids.insert(external_id.to_owned(), id);
+        Some(id)
+    }
+
+    fn lookup(&self, external_id: &str) -> io::Result<usize> {
+        self.ids.get(external_id).cloned()
+            .ok_or_else(|| invalid_data(format!("reference to unknown synset {}", external_id)))
+    }
+}
+
+/// Finds the index of each of the `wanted` columns in the given CSV header row.
+fn csv_columns(header: &str, wanted: &[&str]) -> io::Result<Vec<usize>> {
+    let columns = header.split(',').map(|c| c.trim()).collect::<Vec<_>>();
+    let mut result = Vec::with_capacity(wanted.len());
+    for name in wanted {
+        match columns.iter().position(|c| c == name) {
+            Some(i) => result.push(i),
+            None => return Err(invalid_data(format!("CSV header is missing a {} column", name))),
+        }
+    }
+    Ok(result)
+}
+
+/// Parses CSV files with header rows. Columns may appear in any order and extra columns (e.g. a gloss) are ignored, but
+/// values must not contain commas since quoting is not supported. Synset ids can be any string; nouns within a synset are
+/// separated by spaces and each hypernym row gives one edge.
+pub fn parse_csv<S: BufRead, H: BufRead>(synsets_reader: S, hypernyms_reader: H)
+        -> io::Result<(Vec<Synset>, Vec<(usize, usize)>)> {
+    let mut ids = IdAllocator::new();
+    let mut synsets = Vec::new();
+
+    let mut synset_lines = synsets_reader.lines();
+    let synset_columns = match synset_lines.next() {
+        Some(header) => try!(csv_columns(&try!(header), &["id", "nouns"])),
+        None => return Err(invalid_data("synsets CSV must have a header row".to_owned())),
+    };
+    for line_or_err in synset_lines {
+        let line = try!(line_or_err);
+        if line.trim().len() == 0 {
+            continue;
+        }
+
+        let fields = line.split(',').collect::<Vec<_>>();
+        let field = |column: usize| fields.get(column).map(|f| f.trim())
+            .ok_or_else(|| invalid_data(format!("synsets CSV row has too few columns: {}", line)));
+        let id = try!(field(synset_columns[0]));
+        let nouns = try!(field(synset_columns[1]));
+        let synset_id = try!(ids.allocate(id).ok_or_else(|| invalid_data(format!("duplicate synset id {}", id))));
+        debug_assert_eq!(synset_id, synsets.len());
+        synsets.push(Synset::new(nouns.split(' ').filter(|n| n.len() > 0).map(|n| n.to_owned()).collect()));
+    }
+
+    let mut hypernyms_edges = Vec::new();
+    let mut hypernym_lines = hypernyms_reader.lines();
+    let hypernym_columns = match hypernym_lines.next() {
+        Some(header) => try!(csv_columns(&try!(header), &["synset", "hypernym"])),
+        None => return Err(invalid_data("hypernyms CSV must have a header row".to_owned())),
+    };
+    for line_or_err in hypernym_lines {
+        let line = try!(line_or_err);
+        if line.trim().len() == 0 {
+            continue;
+        }
+
+        let fields = line.split(',').collect::<Vec<_>>();
+        let field = |column: usize| fields.get(column).map(|f| f.trim())
+            .ok_or_else(|| invalid_data(format!("hypernyms CSV row has too few columns: {}", line)));
+        let synset = try!(ids.lookup(try!(field(hypernym_columns[0]))));
+        let hypernym = try!(ids.lookup(try!(field(hypernym_columns[1]))));
+        hypernyms_edges.push((synset, hypernym));
+    }
+
+    Ok((synsets, hypernyms_edges))
+}
+
+/// Parses the Princeton WordNet `data.noun` and `index.noun` files (see the `wndb(5WN)` man page for their layout).
+/// Synsets and their hypernym pointers (including instance hypernyms) come from `data.noun`; the lemmas in `index.noun`
+/// are then added to the synsets they index, which makes the lower-cased spelling of proper nouns available too.
+pub fn parse_princeton<D: BufRead, I: BufRead>(data_reader: D, index_reader: I)
+        -> io::Result<(Vec<Synset>, Vec<(usize, usize)>)> {
+    let mut ids = IdAllocator::new();
+    let mut synset_nouns: Vec<Vec<String>> = Vec::new();
+    let mut hypernym_offsets = Vec::new();
+
+    for line_or_err in data_reader.lines() {
+        let line = try!(line_or_err);
+        if line.starts_with("  ") || line.trim().len() == 0 {
+            continue; // license header
+        }
+
+        // everything after the pipe is the gloss
+        let fields = line.splitn(2, '|').next().expect("splitn yields at least one item")
+            .split(' ').filter(|f| f.len() > 0).collect::<Vec<_>>();
+        let field = |i: usize| fields.get(i).cloned()
+            .ok_or_else(|| invalid_data(format!("data.noun line is truncated: {}", line)));
+
+        let offset = try!(field(0));
+        let id = try!(ids.allocate(offset).ok_or_else(|| invalid_data(format!("duplicate synset offset {}", offset))));
+        debug_assert_eq!(id, synset_nouns.len());
+
+        let word_count = try!(usize::from_str_radix(try!(field(3)), 16)
+            .map_err(|_| invalid_data(format!("word count of synset {} must be hex", offset))));
+        let mut nouns = Vec::with_capacity(word_count);
+        for w in 0..word_count {
+            nouns.push(try!(field(4 + w * 2)).to_owned()); // each word is followed by its lex_id
+        }
+        synset_nouns.push(nouns);
+
+        let pointers_start = 4 + word_count * 2;
+        let pointer_count = try!(try!(field(pointers_start)).parse::<usize>()
+            .map_err(|_| invalid_data(format!("pointer count of synset {} must be a number", offset))));
+        for p in 0..pointer_count {
+            let pointer = pointers_start + 1 + p * 4;
+            let symbol = try!(field(pointer));
+            if (symbol == "@" || symbol == "@i") && try!(field(pointer + 2)) == "n" {
+                hypernym_offsets.push((id, try!(field(pointer + 1)).to_owned()));
+            }
+        }
+    }
+
+    let mut hypernyms_edges = Vec::with_capacity(hypernym_offsets.len());
+    for (id, hypernym_offset) in hypernym_offsets {
+        hypernyms_edges.push((id, try!(ids.lookup(&hypernym_offset))));
+    }
+
+    for line_or_err in index_reader.lines() {
+        let line = try!(line_or_err);
+        if line.starts_with("  ") || line.trim().len() == 0 {
+            continue; // license header
+        }
+
+        let fields = line.split(' ').filter(|f| f.len() > 0).collect::<Vec<_>>();
+        let field = |i: usize| fields.get(i).cloned()
+            .ok_or_else(|| invalid_data(format!("index.noun line is truncated: {}", line)));
+        let number = |i: usize| field(i).and_then(|f| f.parse::<usize>()
+            .map_err(|_| invalid_data(format!("expected a number in index.noun line: {}", line))));
+
+        let lemma = try!(field(0));
+        let synset_count = try!(number(2));
+        let pointer_count = try!(number(3));
+        let offsets_start = 4 + pointer_count + 2; // skip pointer symbols, sense_cnt and tagsense_cnt
+        for s in 0..synset_count {
+            let id = try!(ids.lookup(try!(field(offsets_start + s))));
+            if !synset_nouns[id].iter().any(|n| n == lemma) {
+                synset_nouns[id].push(lemma.to_owned());
+            }
+        }
+    }
+
+    Ok((synset_nouns.into_iter().map(|nouns| Synset::new(nouns)).collect(), hypernyms_edges))
+}
+
+/// Parses a JSON taxonomy of the form:
+///
+/// ```json
+/// {"synsets": [
+///     {"id": "god", "nouns": ["god", "deity"], "hypernyms": ["being"]},
+///     {"id": "being", "nouns": ["being"]}
+/// ]}
+/// ```
+///
+/// Ids may be strings or non-negative integers; `hypernyms` may be omitted for root synsets.
+pub fn parse_json<R: BufRead>(mut reader: R) -> io::Result<(Vec<Synset>, Vec<(usize, usize)>)> {
+    fn id_of(json: &Json) -> io::Result<String> {
+        match *json {
+            Json::String(ref s) => Ok(s.clone()),
+            Json::U64(n) => Ok(n.to_string()),
+            ref other => Err(invalid_data(format!("synset id must be a string or integer, not {}", other))),
+        }
+    }
+
+    let root = try!(Json::from_reader(&mut reader).map_err(|e| invalid_data(format!("invalid JSON: {}", e))));
+    let entries = try!(root.find("synsets").and_then(|s| s.as_array())
+        .ok_or_else(|| invalid_data("JSON taxonomy must have a top level \"synsets\" array".to_owned())));
+
+    let mut ids = IdAllocator::new();
+    let mut synsets = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let id = try!(id_of(try!(entry.find("id")
+            .ok_or_else(|| invalid_data(format!("synset has no id: {}", entry))))));
+        try!(ids.allocate(&id).ok_or_else(|| invalid_data(format!("duplicate synset id {}", id))));
+
+        let nouns = try!(entry.find("nouns").and_then(|n| n.as_array())
+            .ok_or_else(|| invalid_data(format!("synset {} must have a \"nouns\" array", id))));
+        let mut synset_nouns = Vec::with_capacity(nouns.len());
+        for noun in nouns {
+            match noun.as_string() {
+                Some(s) => synset_nouns.push(s.to_owned()),
+                None => return Err(invalid_data(format!("nouns of synset {} must be strings", id))),
+            }
+        }
+        synsets.push(Synset::new(synset_nouns));
+    }
+
+    let mut hypernyms_edges = Vec::new();
+    for (synset_id, entry) in entries.iter().enumerate() {
+        if let Some(hypernyms) = entry.find("hypernyms") {
+            let hypernyms = try!(hypernyms.as_array()
+                .ok_or_else(|| invalid_data(format!("hypernyms must be an array: {}", entry))));
+            for hypernym in hypernyms {
+                hypernyms_edges.push((synset_id, try!(ids.lookup(&try!(id_of(hypernym))))));
+            }
+        }
+    }
+
+    Ok((synsets, hypernyms_edges))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use super::*;
+    use wordnet::Synset;
+
+    fn synset(nouns: &[&str]) -> Synset {
+        Synset::new(nouns.iter().map(|n| n.to_string()).collect())
+    }
+
+    #[test]
+    fn recognises_format_names() {
+        assert_eq!(Format::from_name("princeton"), Some(Format::Princeton));
+        assert_eq!(Format::from_name("json"), Some(Format::Json));
+        assert_eq!(Format::from_name("xml"), None);
+    }
+
+    #[test]
+    fn parses_csv_with_reordered_and_extra_columns() {
+        let synsets = "gloss,nouns,id\na being,being,b\nimmortal being,god deity,g\n";
+        let hypernyms = "hypernym,synset\nb,g\n";
+
+        let (parsed_synsets, edges) = parse_csv(Cursor::new(synsets), Cursor::new(hypernyms)).unwrap();
+
+        assert_eq!(parsed_synsets, vec![synset(&["being"]), synset(&["god", "deity"])]);
+        assert_eq!(edges, vec![(1, 0)]);
+    }
+
+    #[test]
+    fn rejects_csv_referencing_unknown_synset() {
+        let synsets = "id,nouns\n0,being\n";
+        let hypernyms = "synset,hypernym\n0,7\n";
+
+        assert!(parse_csv(Cursor::new(synsets), Cursor::new(hypernyms)).is_err());
+    }
+
+    #[test]
+    fn parses_princeton_data_and_index_files() {
+        let data = concat!(
+            "  1 This software and database is being provided to you, the LICENSEE, by\n",
+            "00001740 03 n 01 entity 0 003 ~ 00001930 n 0000 ~ 00002137 n 0000 ~ 04431553 n 0000 | that which exists\n",
+            "00002137 03 n 02 abstraction 0 abstract_entity 0 001 @ 00001740 n 0000 | a general concept\n",
+            "09536058 18 n 01 Zeus 0 002 @i 00002137 n 0000 ;u 08441203 n 0000 | the supreme god\n",
+        );
+        let index = concat!(
+            "  1 This software and database is being provided to you, the LICENSEE, by\n",
+            "entity n 1 1 ~ 1 1 00001740\n",
+            "zeus n 1 1 @i 1 0 09536058\n",
+        );
+
+        let (synsets, edges) = parse_princeton(Cursor::new(data), Cursor::new(index)).unwrap();
+
+        assert_eq!(synsets, vec![
+            synset(&["entity"]),
+            synset(&["abstraction", "abstract_entity"]),
+            synset(&["Zeus", "zeus"]),
+        ]);
+        assert_eq!(edges, vec![(1, 0), (2, 1)]);
+    }
+
+    #[test]
+    fn parses_json_taxonomy() {
+        let json = r#"{"synsets": [
+            {"id": "god", "nouns": ["god", "deity"], "hypernyms": ["being"]},
+            {"id": "being", "nouns": ["being"]},
+            {"id": 3, "nouns": ["zeus"], "hypernyms": ["god"]}
+        ]}"#;
+
+        let (synsets, edges) = parse_json(Cursor::new(json)).unwrap();
+
+        assert_eq!(synsets, vec![synset(&["god", "deity"]), synset(&["being"]), synset(&["zeus"])]);
+        assert_eq!(edges, vec![(0, 1), (2, 0)]);
+    }
+
+    #[test]
+    fn rejects_json_without_synsets_array() {
+        assert!(parse_json(Cursor::new(r#"{"nodes": []}"#)).is_err());
+    }
+}
@@ -1,32 +1,107 @@
 #![feature(core)] // allows using sum() on iterators
 
+extern crate getopts;
+extern crate graph;
+extern crate rustc_serialize;
+#[cfg(test)]
+extern crate quickcheck;
+
+use formats::Format;
+use getopts::Options;
 use std::env;
-use wordnet::WordNet;
+use std::io::{self, Write};
+use std::process;
+use wordnet::Progress;
 
 mod bfdp;
 mod digraph;
+mod formats;
 mod outcast;
 mod sap;
+mod stats;
 mod wordnet;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    let usage = format!("Usage: {} <synsets-file> <hypernyms-file> <outcast-file-1> [... <outcast-file-n>]", args[0]);
-    if args.len() < 4 {
-        println!("Error: incorrect number of arguments provided.\n{}", usage);
+    let program = &args[0];
+
+    fn print_usage(program: &str, opts: Options) {
+        let brief = format!("Usage: {} [options] <graph-file-1> [<graph-file-2>] [<outcast-file-1> ... <outcast-file-n>]\n\
+            \n\
+            Graph files per format:\n\
+            \x20   coursera:  <synsets.txt> <hypernyms.txt>\n\
+            \x20   csv:       <synsets.csv> <hypernyms.csv>\n\
+            \x20   princeton: <data.noun> <index.noun>\n\
+            \x20   json:      <taxonomy.json>", program);
+        print!("{}", opts.usage(&brief));
+    }
+
+    let mut opts = Options::new();
+    opts.optopt("f", "format", "format of the graph files: coursera (default), csv, princeton or json", "FORMAT");
+    opts.optflag("s", "stats", "print statistics about the synset graph (outcast files become optional)");
+    opts.optflag("h", "help", "print this usage information");
+
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            println!("Invalid arguments: {}", f.to_string());
+            print_usage(program, opts);
+            process::exit(1);
+        },
+    };
+
+    if matches.opt_present("h") {
+        print_usage(program, opts);
         return;
     }
 
-    let (synsets_path, hypernyms_path) = (&args[1], &args[2]);
-    match WordNet::create_by_parsing_files(synsets_path, hypernyms_path) {
-        Ok(wordnet) => for outcast_path in args.iter().skip(3) {
-            print!("{} ", outcast_path);
-            match read_nouns(outcast_path) {
-                Ok(nouns) => println!("outcast is {} (nouns: {:?})", outcast::find_outcast(&wordnet, &nouns), &nouns),
-                Err(parse_err) => panic!("Cannot read nouns from {}; {}", outcast_path, parse_err),
+    let format = match matches.opt_str("f") {
+        None => Format::Coursera,
+        Some(name) => match Format::from_name(&name) {
+            Some(format) => format,
+            None => {
+                println!("Error: unknown format {}.", name);
+                print_usage(program, opts);
+                process::exit(1);
+            },
+        },
+    };
+
+    let print_stats = matches.opt_present("s");
+    let min_outcast_files = if print_stats { 0 } else { 1 };
+    if matches.free.len() < format.file_count() + min_outcast_files {
+        println!("Error: incorrect number of arguments provided.");
+        print_usage(program, opts);
+        process::exit(1);
+    }
+
+    let (graph_paths, outcast_paths) = matches.free.split_at(format.file_count());
+    print!("Parsing {:?} graph from {:?}", format, graph_paths);
+    let parsed = {
+        let mut print_progress = |_: Progress| {
+            print!(".");
+            io::stdout().flush().unwrap();
+        };
+        formats::parse_files(format, graph_paths, Some(&mut print_progress as &mut FnMut(Progress)))
+    };
+    match parsed {
+        Ok(wordnet) => {
+            println!("done!");
+            if print_stats {
+                print!("{}", stats::calculate(&wordnet));
+            }
+            for outcast_path in outcast_paths {
+                print!("{} ", outcast_path);
+                match read_nouns(outcast_path) {
+                    Ok(nouns) => println!("outcast is {} (nouns: {:?})", outcast::find_outcast(&wordnet, &nouns), &nouns),
+                    Err(parse_err) => panic!("Cannot read nouns from {}; {}", outcast_path, parse_err),
+                }
             }
         },
-        Err(parse_err) => panic!("Failed parsing synsets or hypernyms: {}", parse_err),
+        Err(parse_err) => {
+            println!("failed!");
+            panic!("Failed parsing synsets or hypernyms: {}", parse_err)
+        },
     }
 
     println!("Finished.");
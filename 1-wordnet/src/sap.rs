@@ -1,5 +1,6 @@
 use bfdp;
 use digraph::Digraph;
+use graph::Graph;
 
 /// Calculates the shortest ancestral path between vertices of a digraph.
 /// Returns the distance - number of edges between those two points when taking the path that passes through the common
@@ -39,8 +40,123 @@ pub fn path_stats_between(g: &Digraph, vs: Vec<usize>, ws: Vec<usize>) -> Option
 #[cfg(test)]
 mod tests {
     use digraph::Digraph;
+    use graph::Graph;
+    use quickcheck::{quickcheck, TestResult};
+    use std::cmp;
     use super::*;
 
+    const MAX_RANDOM_VERTICES: u8 = 20;
+
+    /// Builds a DAG of `n` vertices by directing each of the given edges from its lower to its higher vertex (after
+    /// wrapping vertices to be < n), so random input can never introduce a cycle. Self loops are dropped.
+    fn random_dag(n: usize, edges: &[(u8, u8)]) -> Digraph {
+        let mut g = Digraph::new(n);
+        for &(a, b) in edges {
+            let (a, b) = (a as usize % n, b as usize % n);
+            if a != b {
+                g.add_edge(cmp::min(a, b), cmp::max(a, b));
+            }
+        }
+        g
+    }
+
+    /// Computes the shortest ancestral path the slow but obviously correct way: Floyd-Warshall all-pairs shortest
+    /// paths, then try every combination of source, destination and common ancestor.
+    fn brute_force_path_between(g: &Digraph, vs: &[usize], ws: &[usize]) -> Option<i32> {
+        let n = g.vertices();
+        let mut dist = vec![vec![None; n]; n];
+        for v in 0..n {
+            dist[v][v] = Some(0);
+            for &w in g.adj(v) {
+                if v != w {
+                    dist[v][w] = Some(1);
+                }
+            }
+        }
+        for k in 0..n {
+            for i in 0..n {
+                for j in 0..n {
+                    if let (Some(ik), Some(kj)) = (dist[i][k], dist[k][j]) {
+                        if dist[i][j].map_or(true, |ij| ik + kj < ij) {
+                            dist[i][j] = Some(ik + kj);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut best = None;
+        for &v in vs {
+            for &w in ws {
+                for ancestor in 0..n {
+                    if let (Some(va), Some(wa)) = (dist[v][ancestor], dist[w][ancestor]) {
+                        if best.map_or(true, |b| va + wa < b) {
+                            best = Some(va + wa);
+                        }
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    fn wrap_vertices(vertices: &[u8], n: usize) -> Vec<usize> {
+        vertices.iter().map(|&v| v as usize % n).collect()
+    }
+
+    #[test]
+    fn matches_brute_force_on_random_dags() {
+        fn prop(n: u8, edges: Vec<(u8, u8)>, vs: Vec<u8>, ws: Vec<u8>) -> TestResult {
+            if vs.is_empty() || ws.is_empty() {
+                return TestResult::discard();
+            }
+            let n = (n % MAX_RANDOM_VERTICES) as usize + 1;
+            let g = random_dag(n, &edges);
+            let (vs, ws) = (wrap_vertices(&vs, n), wrap_vertices(&ws, n));
+
+            let expected = brute_force_path_between(&g, &vs, &ws);
+            TestResult::from_bool(path_stats_between(&g, vs, ws) == expected)
+        }
+        quickcheck(prop as fn(u8, Vec<(u8, u8)>, Vec<u8>, Vec<u8>) -> TestResult);
+    }
+
+    #[test]
+    fn finds_zero_length_path_between_vertex_and_itself() {
+        fn prop(n: u8, edges: Vec<(u8, u8)>, v: u8) -> bool {
+            let n = (n % MAX_RANDOM_VERTICES) as usize + 1;
+            let g = random_dag(n, &edges);
+            let v = v as usize % n;
+
+            path_stats_between(&g, vec![v], vec![v]) == Some(0)
+        }
+        quickcheck(prop as fn(u8, Vec<(u8, u8)>, u8) -> bool);
+    }
+
+    #[test]
+    fn finds_no_path_between_disconnected_components() {
+        // vertices < half can only reach other vertices < half, so sources from each half never share an ancestor
+        fn prop(n: u8, edges: Vec<(u8, u8)>, vs: Vec<u8>, ws: Vec<u8>) -> TestResult {
+            if vs.is_empty() || ws.is_empty() {
+                return TestResult::discard();
+            }
+            let half = (n % MAX_RANDOM_VERTICES) as usize + 1;
+            let mut g = random_dag(half * 2, &[]);
+            for &(a, b) in edges.iter() {
+                let (a, b) = (a as usize % half, b as usize % half);
+                if a != b {
+                    g.add_edge(cmp::min(a, b), cmp::max(a, b));
+                    g.add_edge(half + cmp::min(a, b), half + cmp::max(a, b));
+                }
+            }
+            let vs = wrap_vertices(&vs, half);
+            let ws = wrap_vertices(&ws, half).into_iter().map(|w| w + half).collect::<Vec<_>>();
+
+            let expected = brute_force_path_between(&g, &vs, &ws);
+            TestResult::from_bool(expected == None && path_stats_between(&g, vs, ws) == None)
+        }
+        quickcheck(prop as fn(u8, Vec<(u8, u8)>, Vec<u8>, Vec<u8>) -> TestResult);
+    }
+
     #[test]
     fn should_find_shortest_path_in_trivial_case() {
         let g = Digraph::new(1);
@@ -0,0 +1,187 @@
+use bfdp;
+use digraph::Digraph;
+use graph::Graph;
+use std::fmt;
+use wordnet::WordNet;
+
+/// How many of the most polysemous nouns to report.
+const TOP_POLYSEMOUS_NOUNS: usize = 10;
+
+/// Summary statistics of a WordNet's synset graph, used to sanity check a taxonomy before deploying it.
+#[derive(Debug, PartialEq)]
+pub struct GraphStats {
+    pub synsets: usize,
+    pub nouns: usize,
+    pub edges: usize,
+    pub roots: usize, // synsets without any hypernyms
+    pub multiple_hypernyms: usize, // synsets with more than one hypernym
+    pub unreachable: usize, // synsets which can't reach a root (only possible if the graph has cycles)
+    pub max_depth: Option<i32>,
+    pub mean_depth: Option<f64>,
+    pub depth_histogram: Vec<usize>, // index = depth, i.e. distance from the nearest root
+    pub most_polysemous: Vec<(String, usize)>, // nouns with the most synsets, most polysemous first
+    pub diameter_estimate: i32,
+}
+
+pub fn calculate(wordnet: &WordNet) -> GraphStats {
+    let g = wordnet.hypernyms();
+
+    let roots = (0..g.vertices()).filter(|&v| g.outdegree(v) == 0).collect::<Vec<_>>();
+    let multiple_hypernyms = (0..g.vertices()).filter(|&v| g.outdegree(v) > 1).count();
+
+    // depth of a synset is its distance from the nearest root, so search down from all roots at once
+    let from_roots = bfdp::search(&g.reverse(), roots.clone());
+    let mut depth_histogram = Vec::new();
+    let mut unreachable = 0;
+    let mut depth_total = 0u64;
+    for v in 0..g.vertices() {
+        match from_roots.dist_to(v) {
+            Some(depth) => {
+                let depth = depth as usize;
+                while depth_histogram.len() <= depth {
+                    depth_histogram.push(0);
+                }
+                depth_histogram[depth] += 1;
+                depth_total += depth as u64;
+            },
+            None => unreachable += 1,
+        }
+    }
+    let reachable = g.vertices() - unreachable;
+
+    let mut polysemous = wordnet.nouns().into_iter()
+        .map(|noun| (noun.clone(), wordnet.polysemy(noun)))
+        .collect::<Vec<_>>();
+    polysemous.sort_by(|a, b| (b.1, &a.0).cmp(&(a.1, &b.0))); // most synsets first, then alphabetically
+    polysemous.truncate(TOP_POLYSEMOUS_NOUNS);
+
+    GraphStats {
+        synsets: g.vertices(),
+        nouns: wordnet.nouns().len(),
+        edges: g.edges(),
+        roots: roots.len(),
+        multiple_hypernyms: multiple_hypernyms,
+        unreachable: unreachable,
+        max_depth: if depth_histogram.is_empty() { None } else { Some(depth_histogram.len() as i32 - 1) },
+        mean_depth: if reachable == 0 { None } else { Some(depth_total as f64 / reachable as f64) },
+        depth_histogram: depth_histogram,
+        most_polysemous: polysemous,
+        diameter_estimate: estimate_diameter(g),
+    }
+}
+
+/// Estimates the diameter of the graph (ignoring edge direction) with a double sweep: search from synset 0 to find the
+/// synset furthest from it, then report the distance to whatever is furthest from that synset. This is a lower bound on
+/// the true diameter, which is exact for trees and usually close for taxonomies, and needs only 2 searches.
+fn estimate_diameter(g: &Digraph) -> i32 {
+    if g.vertices() == 0 {
+        return 0;
+    }
+
+    let mut undirected = g.reverse();
+    for v in 0..g.vertices() {
+        for &w in g.adj(v) {
+            undirected.add_edge(v, w);
+        }
+    }
+
+    let furthest_from = |source: usize| {
+        let result = bfdp::search(&undirected, vec![source]);
+        let mut furthest = (source, 0);
+        for v in 0..undirected.vertices() {
+            match result.dist_to(v) {
+                Some(dist) if dist > furthest.1 => furthest = (v, dist),
+                _ => (),
+            }
+        }
+        furthest
+    };
+
+    let (first_sweep_end, _) = furthest_from(0);
+    furthest_from(first_sweep_end).1
+}
+
+impl fmt::Display for GraphStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(writeln!(f, "Synsets:                   {}", self.synsets));
+        try!(writeln!(f, "Nouns:                     {}", self.nouns));
+        try!(writeln!(f, "Hypernym edges:            {}", self.edges));
+        try!(writeln!(f, "Roots:                     {}", self.roots));
+        try!(writeln!(f, "Synsets with >1 hypernym:  {}", self.multiple_hypernyms));
+        try!(writeln!(f, "Synsets without a root:    {}", self.unreachable));
+        match (self.max_depth, self.mean_depth) {
+            (Some(max), Some(mean)) => {
+                try!(writeln!(f, "Max depth:                 {}", max));
+                try!(writeln!(f, "Mean depth:                {:.2}", mean));
+            },
+            _ => try!(writeln!(f, "Max/mean depth:            n/a (no synset can reach a root)")),
+        }
+        try!(writeln!(f, "Diameter (estimate):       {}", self.diameter_estimate));
+        try!(writeln!(f, "Depth histogram:"));
+        for (depth, count) in self.depth_histogram.iter().enumerate() {
+            try!(writeln!(f, "  {:>3}: {}", depth, count));
+        }
+        try!(writeln!(f, "Most polysemous nouns:"));
+        for &(ref noun, synsets) in self.most_polysemous.iter() {
+            try!(writeln!(f, "  {} ({} synsets)", noun, synsets));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::calculate;
+    use wordnet::{Synset, WordNet};
+
+    fn synset(nouns: &[&str]) -> Synset {
+        Synset::new(nouns.iter().map(|n| n.to_string()).collect())
+    }
+
+    #[test]
+    fn calculates_stats_of_small_taxonomy() {
+        // Graph (edges point to hypernyms):
+        // 0 being <- 1 god <- 2 mars
+        //          \- 3 person <- 4 hero <- 5 demigod -> 1 god
+        let w = WordNet::create_from_synsets_and_hypernyms(
+            vec![
+                synset(&["being"]),
+                synset(&["god", "deity"]),
+                synset(&["mars", "ares"]),
+                synset(&["person", "mortal"]),
+                synset(&["hero", "mortal"]),
+                synset(&["demigod", "hero"]),
+            ],
+            vec![(1, 0), (2, 1), (3, 0), (4, 3), (5, 4), (5, 1)]
+        );
+
+        let stats = calculate(&w);
+
+        assert_eq!(stats.synsets, 6);
+        assert_eq!(stats.nouns, 9);
+        assert_eq!(stats.edges, 6);
+        assert_eq!(stats.roots, 1);
+        assert_eq!(stats.multiple_hypernyms, 1);
+        assert_eq!(stats.unreachable, 0);
+        assert_eq!(stats.max_depth, Some(2));
+        assert_eq!(stats.mean_depth, Some(8.0 / 6.0));
+        assert_eq!(stats.depth_histogram, vec![1, 2, 3]);
+        assert_eq!(&stats.most_polysemous[..2], &[("hero".to_string(), 2), ("mortal".to_string(), 2)]);
+        assert_eq!(stats.diameter_estimate, 3); // e.g. mars -> god -> being -> person
+    }
+
+    #[test]
+    fn counts_synsets_in_cycles_as_unreachable() {
+        let w = WordNet::create_from_synsets_and_hypernyms(
+            vec![synset(&["a"]), synset(&["b"])],
+            vec![(0, 1), (1, 0)]
+        );
+
+        let stats = calculate(&w);
+
+        assert_eq!(stats.roots, 0);
+        assert_eq!(stats.unreachable, 2);
+        assert_eq!(stats.max_depth, None);
+        assert_eq!(stats.mean_depth, None);
+    }
+}
@@ -1,6 +1,8 @@
 use std::borrow::ToOwned;
 use std::collections::{HashMap, HashSet};
 use std::io;
+use std::io::prelude::*;
+use std::path::Path;
 use digraph::Digraph;
 use sap;
 
@@ -23,17 +25,37 @@ pub struct WordNet {
     hypernyms: Digraph, // index = the id of the synset
 }
 
+/// Reported to the optional progress callback of `WordNet::create_from_readers` every `PROGRESS_INTERVAL` lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    Synsets(usize), // number of synsets parsed so far
+    Hypernyms(usize), // number of hypernym lines parsed so far
+}
+
+pub const PROGRESS_INTERVAL: usize = 1000;
+
+pub fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
 impl WordNet {
-    pub fn create_by_parsing_files(synsets_path: &String, hypernyms_path: &String) -> io::Result<WordNet> {
+    pub fn create_by_parsing_files<P: AsRef<Path>>(synsets_path: P, hypernyms_path: P) -> io::Result<WordNet> {
         use std::io::BufReader;
-        use std::io::prelude::*;
         use std::fs::File;
 
-        print!("Parsing synsets from {}", synsets_path);
-        let synsets_file = BufReader::new(try!(File::open(synsets_path)));
-        let mut synsets = Vec::with_capacity(100000);
-        for line_or_err in synsets_file.lines() {
-            let line = line_or_err.unwrap();
+        WordNet::create_from_readers(
+            BufReader::new(try!(File::open(synsets_path))),
+            BufReader::new(try!(File::open(hypernyms_path))),
+            None)
+    }
+
+    /// Parses synsets and hypernyms in the Coursera `synsets.txt`/`hypernyms.txt` layout from any buffered readers,
+    /// calling `progress` (if given) as parsing proceeds. Malformed input results in an `InvalidData` error.
+    pub fn create_from_readers<S: BufRead, H: BufRead>(synsets_reader: S, hypernyms_reader: H,
+            mut progress: Option<&mut FnMut(Progress)>) -> io::Result<WordNet> {
+        let mut synsets = Vec::new();
+        for line_or_err in synsets_reader.lines() {
+            let line = try!(line_or_err);
 
             if line.len() == 0 {
                 break; // end of file
@@ -41,29 +63,30 @@ impl WordNet {
 
             let (synset_id, synset) = {
                 let columns = &mut line.splitn(3, ",");
-                let id = columns.next().expect("synset must have id").parse::<usize>().ok().expect("synset id must be an int");
-                let nouns = columns.next().expect("synset must have nouns");
+                let id = try!(columns.next().and_then(|id| id.parse::<usize>().ok())
+                    .ok_or_else(|| invalid_data(format!("synset id must be an int: {}", line))));
+                let nouns = try!(columns.next()
+                    .ok_or_else(|| invalid_data(format!("synset must have nouns: {}", line))));
                 (id, Synset {
                         nouns: nouns.split(" ").map(|s| s.to_owned()).collect::<Vec<_>>(),
                     }
                 )
             };
+            if synset_id != synsets.len() {
+                return Err(invalid_data(format!("expected synset {} but found synset {}", synsets.len(), synset_id)));
+            }
             synsets.push(synset);
-            assert_eq!(synset_id, synsets.len() - 1);
 
-            if synset_id % 1000 == 0 {
-                print!(".");
+            if synsets.len() % PROGRESS_INTERVAL == 0 {
+                if let Some(ref mut callback) = progress {
+                    callback(Progress::Synsets(synsets.len()));
+                }
             }
         }
-        println!("done!");
 
-        // parse hypernyms
-        print!("Parsing hypernyms from {}", hypernyms_path);
-        let hypernyms_file = BufReader::new(try!(File::open(hypernyms_path)));
-
-        let mut hypernyms_edges = Vec::with_capacity(100000);
-        for (i, line_or_err) in hypernyms_file.lines().enumerate() {
-            let line = line_or_err.unwrap();
+        let mut hypernyms_edges = Vec::new();
+        for (i, line_or_err) in hypernyms_reader.lines().enumerate() {
+            let line = try!(line_or_err);
 
             if line.len() == 0 {
                 break; // end of file
@@ -71,17 +94,25 @@ impl WordNet {
 
             let mut synset = None;
             for id_str in line.split(",") {
-                let id = id_str.parse().ok().expect("should only be digits in hypernyms file");
+                let id = try!(id_str.parse().ok()
+                    .ok_or_else(|| invalid_data(format!("should only be digits in hypernyms line: {}", line))));
                 match synset {
                     None => synset = Some(id),
                     Some(synset_id) => hypernyms_edges.push((synset_id, id)),
                 }
             }
-            if i % 1000 == 0 {
-                print!(".");
+            if (i + 1) % PROGRESS_INTERVAL == 0 {
+                if let Some(ref mut callback) = progress {
+                    callback(Progress::Hypernyms(i + 1));
+                }
+            }
+        }
+
+        for &(a, b) in hypernyms_edges.iter() {
+            if a >= synsets.len() || b >= synsets.len() {
+                return Err(invalid_data(format!("hypernym edge {} -> {} refers to an unknown synset", a, b)));
             }
         }
-        println!("done!");
 
         Ok(WordNet::create_from_synsets_and_hypernyms(synsets, hypernyms_edges))
     }
@@ -119,6 +150,16 @@ impl WordNet {
         self.nouns_to_synsets.contains_key(word)
     }
 
+    /// The number of synsets which contain the given noun (0 if it's not a known noun).
+    pub fn polysemy(&self, noun: &String) -> usize {
+        self.nouns_to_synsets.get(noun).map(|synsets| synsets.len()).unwrap_or(0)
+    }
+
+    /// The graph of synsets, with an edge from each synset to each of its hypernyms.
+    pub fn hypernyms(&self) -> &Digraph {
+        &self.hypernyms
+    }
+
     /// Get the distance between the given 2 nouns.
     /// Originally this calculated the common ancestor too, but that was unnecessary for wordnet so removed it for speed
     /// reasons. See code at the tag 1-wordnet-1.0 for that.
@@ -136,7 +177,8 @@ impl WordNet {
 
 #[cfg(test)]
 mod tests {
-    use super::{Synset, WordNet};
+    use std::io::Cursor;
+    use super::{Progress, PROGRESS_INTERVAL, Synset, WordNet};
 
     #[test]
     fn should_have_no_nouns_initially() {
@@ -186,4 +228,46 @@ mod tests {
         assert_eq!(w.relationship(&"ares".to_string(), &"god".to_string()),
             1);
     }
+
+    #[test]
+    fn should_create_from_in_memory_readers() {
+        let synsets = "0,mars ares,roman god of war\n1,god,immortal being\n2,zeus,greek god\n";
+        let hypernyms = "0,1\n2,1\n";
+
+        let w = WordNet::create_from_readers(Cursor::new(synsets), Cursor::new(hypernyms), None).unwrap();
+
+        assert!(w.is_noun(&"ares".to_string()));
+        assert_eq!(w.relationship(&"mars".to_string(), &"zeus".to_string()), 2);
+    }
+
+    #[test]
+    fn should_report_progress_to_callback() {
+        let synsets = (0..(PROGRESS_INTERVAL * 2 + 1)).map(|i| format!("{},noun{},gloss\n", i, i))
+            .collect::<String>();
+        let hypernyms = (1..(PROGRESS_INTERVAL + 1)).map(|i| format!("{},0\n", i)).collect::<String>();
+        let mut reported = Vec::new();
+
+        {
+            let mut record = |p: Progress| reported.push(p);
+            WordNet::create_from_readers(Cursor::new(synsets), Cursor::new(hypernyms),
+                Some(&mut record as &mut FnMut(Progress))).unwrap();
+        }
+
+        assert_eq!(reported, vec![
+            Progress::Synsets(PROGRESS_INTERVAL),
+            Progress::Synsets(PROGRESS_INTERVAL * 2),
+            Progress::Hypernyms(PROGRESS_INTERVAL),
+        ]);
+    }
+
+    #[test]
+    fn should_reject_malformed_input_instead_of_panicking() {
+        let out_of_order = WordNet::create_from_readers(Cursor::new("1,god,gloss\n"), Cursor::new(""), None);
+        let bad_hypernym = WordNet::create_from_readers(Cursor::new("0,god,gloss\n"), Cursor::new("0,x\n"), None);
+        let unknown_synset = WordNet::create_from_readers(Cursor::new("0,god,gloss\n"), Cursor::new("0,5\n"), None);
+
+        assert!(out_of_order.is_err());
+        assert!(bad_hypernym.is_err());
+        assert!(unknown_synset.is_err());
+    }
 }
@@ -0,0 +1,263 @@
+use carving::{Carver, CarverOptions};
+use crossbeam;
+use picture::{self, Format, Picture, Pixels};
+use std::cmp;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use {retarget_with, Error, Image};
+
+/// The size to resize every image of a batch to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Size {
+    Exact(usize, usize), // width and height
+    Percent(usize), // of each image's width and height
+}
+
+impl Size {
+    /// Parses `WIDTHxHEIGHT` or a percentage such as `75%`; neither can be 0.
+    pub fn from_spec(spec: &str) -> Option<Size> {
+        if spec.ends_with('%') {
+            return match spec[..spec.len() - 1].parse() {
+                Ok(percent) if percent > 0 => Some(Size::Percent(percent)),
+                _ => None,
+            };
+        }
+        let mut parts = spec.splitn(2, 'x');
+        match (parts.next().and_then(|w| w.parse().ok()), parts.next().and_then(|h| h.parse().ok())) {
+            (Some(width), Some(height)) if width > 0 && height > 0 => Some(Size::Exact(width, height)),
+            _ => None,
+        }
+    }
+
+    /// The size a `width` x `height` image should be resized to (rounding percentages to the nearest pixel, but
+    /// leaving at least 1).
+    pub fn for_image(&self, width: usize, height: usize) -> (usize, usize) {
+        match *self {
+            Size::Exact(new_width, new_height) => (new_width, new_height),
+            Size::Percent(percent) => {
+                let scale = |length: usize| cmp::max(1, (length * percent + 50) / 100);
+                (scale(width), scale(height))
+            },
+        }
+    }
+}
+
+/// What happened to 1 image of a batch.
+#[derive(Debug)]
+pub struct Outcome {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub result: Result<((usize, usize), (usize, usize)), Error>, // the image's original and new sizes
+    pub duration: Duration, // how long loading, carving and saving it took
+}
+
+/// The image files `spec` names: every file in it if it's a directory, otherwise the files in its parent directory
+/// whose names match its last component, where `*` matches any run of characters and `?` any 1 character (so a
+/// plain file name matches just that file). Sorted by path.
+pub fn find_inputs(spec: &str) -> Result<Vec<PathBuf>, Error> {
+    let path = Path::new(spec);
+    let (directory, pattern) = if path.is_dir() {
+        (path, "*")
+    } else {
+        let directory = match path.parent() {
+            Some(parent) if parent != Path::new("") => parent,
+            _ => Path::new("."),
+        };
+        (directory, path.file_name().and_then(|name| name.to_str()).unwrap_or(""))
+    };
+    let entries = try!(fs::read_dir(directory)
+        .map_err(|e| Error::Io(format!("could not read {}, because: {}", directory.display(), e))));
+    let mut inputs = entries.filter_map(|entry| entry.ok()).map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter(|path| path.file_name().and_then(|name| name.to_str()).map_or(false, |name| matches(pattern, name)))
+        .collect::<Vec<_>>();
+    inputs.sort();
+    Ok(inputs)
+}
+
+/// Whether saving the carved images in `output_directory` would write over any of `inputs`, since they keep their
+/// names. Directories are compared once symbolic links and relative parts have been resolved, so `.` and the
+/// directory's full path count as the same; an output directory which doesn't exist yet can't hold any inputs.
+pub fn writes_over_inputs(inputs: &[PathBuf], output_directory: &Path) -> bool {
+    let output_directory = match fs::canonicalize(output_directory) {
+        Ok(directory) => directory,
+        Err(_) => return false,
+    };
+    inputs.iter().filter_map(|input| input.parent()).any(|directory| {
+        let directory = if directory == Path::new("") { Path::new(".") } else { directory };
+        fs::canonicalize(directory).map_or(false, |directory| directory == output_directory)
+    })
+}
+
+/// Resizes each of the images to `size` with `retarget`, saving them under the same names in `output_directory`
+/// (which must exist) in `format`, or the format each was loaded from. The images are shared out between `workers`
+/// threads as they become free, each with its own carver which is reused for every image it carves, and each
+/// carver gets an equal share of `options.threads`. A failure only affects its own image.
+///
+/// Returns what happened to each image, in the same order as `inputs`.
+pub fn carve_files(inputs: &[PathBuf], output_directory: &Path, size: Size, format: Option<Format>,
+        options: CarverOptions, workers: usize) -> Vec<Outcome> {
+    let workers = cmp::max(1, cmp::min(workers, inputs.len()));
+    let options = CarverOptions { threads: cmp::max(1, options.threads / workers), ..options };
+    let next = AtomicUsize::new(0);
+    let next = &next;
+    let mut outcomes = crossbeam::scope(|scope| {
+        let handles = (0..workers).map(|_| scope.spawn(move || {
+            let mut carver = Carver::with_options(0, options);
+            let mut outcomes = Vec::new();
+            loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                let input = match inputs.get(i) {
+                    Some(input) => input,
+                    None => return outcomes,
+                };
+                let output = output_directory.join(input.file_name().unwrap_or(input.as_os_str()));
+                let start = Instant::now();
+                let result = carve_file(&mut carver, input, &output, size, format, options);
+                outcomes.push((i, Outcome { input: input.clone(), output: output, result: result,
+                    duration: start.elapsed() }));
+            }
+        })).collect::<Vec<_>>();
+        handles.into_iter().flat_map(|handle| handle.join()).collect::<Vec<_>>()
+    });
+    outcomes.sort_by_key(|&(i, _)| i);
+    outcomes.into_iter().map(|(_, outcome)| outcome).collect()
+}
+
+fn carve_file(carver: &mut Carver, input: &Path, output: &Path, size: Size, format: Option<Format>,
+        options: CarverOptions) -> Result<((usize, usize), (usize, usize)), Error> {
+    let (picture, input_format) = try!(picture::load(input));
+    let Picture { pixels, width, height } = picture;
+    let (new_width, new_height) = size.for_image(width, height);
+    // carve the pixels in whatever format they were decoded in, so they can be saved the same way
+    macro_rules! retarget_each_format {
+        ($($format:ident),*) => {
+            match pixels {
+                $(Pixels::$format(pixels) => Pixels::$format(try!(
+                    retarget_with(carver, &Image::new(pixels, width, height), new_width, new_height, options)
+                ).pixels),)*
+            }
+        }
+    }
+    let pixels = retarget_each_format!(Grey, Grey16, GreyAlpha, GreyAlpha16, Rgb, Rgb16, Rgba, Rgba16);
+    let carved = Picture { pixels: pixels, width: new_width, height: new_height };
+    try!(picture::save(output, format.unwrap_or(input_format), &carved));
+    Ok(((width, height), (new_width, new_height)))
+}
+
+/// Whether `name` matches the wildcard `pattern` (see `find_inputs`). Only the most recent `*` is ever backtracked to,
+/// since any match found by giving an earlier one more characters can also be found by giving them to the later one,
+/// so this takes at most `pattern.len() * name.len()` steps.
+fn matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    let (mut p, mut n) = (0, 0);
+    // the pattern after the most recent star, and how much of the name that star has matched up to
+    let mut star = None;
+    while n < name.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            p += 1;
+            star = Some((p, n));
+        } else if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if let Some((after_star, matched_to)) = star {
+            // let the star match 1 more character, and try the rest of the pattern again from there
+            p = after_star;
+            n = matched_to + 1;
+            star = Some((after_star, n));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use carving::CarverOptions;
+    use lodepng::Grey;
+    use noisy_image;
+    use picture::{self, Format, Picture, Pixels};
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::path::PathBuf;
+    use std::process;
+    use super::{carve_files, find_inputs, matches, writes_over_inputs, Size};
+
+    /// A temporary directory for a test's files, unique to this process so that concurrent test runs can't interfere,
+    /// which is removed (along with everything in it) when it's dropped, even if the test fails.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let path = env::temp_dir().join(format!("{}-{}", name, process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(Size::from_spec("120x80"), Some(Size::Exact(120, 80)));
+        assert_eq!(Size::from_spec("75%"), Some(Size::Percent(75)));
+        assert_eq!(Size::from_spec("0%"), None);
+        assert_eq!(Size::from_spec("120x0"), None);
+        assert_eq!(Size::from_spec("120"), None);
+
+        assert_eq!(Size::Percent(75).for_image(10, 3), (8, 2));
+        assert_eq!(Size::Percent(10).for_image(4, 20), (1, 2));
+        assert_eq!(Size::Exact(5, 6).for_image(10, 3), (5, 6));
+    }
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(matches("*.png", "photo.png"));
+        assert!(matches("*", ""));
+        assert!(matches("a?c*", "abc"));
+        assert!(matches("a*b*c", "aXbYbc"));
+        assert!(!matches("*.png", "photo.pgm"));
+        assert!(!matches("a?c", "ac"));
+        assert!(matches("*a", "*ba"));
+        assert!(matches("**.png", "photo.png"));
+        assert!(!matches("a*a*a*a*a*a*a*a*b", &"a".repeat(100)));
+    }
+
+    #[test]
+    fn carves_every_matching_file_and_reports_failures() {
+        let temp = TempDir::new("seam-carving-batch-test");
+        let directory = &temp.0;
+        let output = directory.join("out");
+        fs::create_dir(&output).unwrap();
+        for &(name, width, height) in [("a.pgm", 6, 4), ("b.pgm", 10, 5)].iter() {
+            let pixels = noisy_image(width, height).iter().map(|noise| Grey(noise.r)).collect();
+            let picture = Picture { pixels: Pixels::Grey(pixels), width: width, height: height };
+            picture::save(&directory.join(name), Format::Pnm, &picture).unwrap();
+        }
+        File::create(directory.join("c.pgm")).unwrap().write_all(b"not an image").unwrap();
+
+        let inputs = find_inputs(directory.join("*.pgm").to_str().unwrap()).unwrap();
+        let outcomes = carve_files(&inputs, &output, Size::Percent(50), None, CarverOptions::default(), 2);
+
+        assert_eq!(outcomes.iter().map(|outcome| outcome.input.clone()).collect::<Vec<_>>(), inputs);
+        assert_eq!(outcomes[0].result, Ok(((6, 4), (3, 2))));
+        assert_eq!(outcomes[1].result, Ok(((10, 5), (5, 3))));
+        assert!(outcomes[2].result.is_err());
+        let (carved, _) = picture::load(&output.join("b.pgm")).unwrap();
+        assert_eq!((carved.width, carved.height), (5, 3));
+        assert!(!output.join("c.pgm").exists());
+        assert!(!writes_over_inputs(&inputs, &output));
+        assert!(writes_over_inputs(&inputs, &output.join("..")));
+        assert!(!writes_over_inputs(&inputs, &directory.join("missing")));
+    }
+}
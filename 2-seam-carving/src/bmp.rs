@@ -0,0 +1,194 @@
+use lodepng::{RGB, RGBA};
+use picture::{Picture, Pixels};
+
+const FILE_HEADER_SIZE: usize = 14;
+const INFO_HEADER_SIZE: usize = 40; // BITMAPINFOHEADER
+const BI_RGB: u32 = 0; // uncompressed
+
+/// Decodes an uncompressed 24 or 32 bit BMP. 32 bit images are only treated as transparent if they have any non-zero
+/// alpha, since many writers leave the 4th byte as 0.
+pub fn decode(data: &[u8]) -> Result<Picture, String> {
+    if data.len() < FILE_HEADER_SIZE + INFO_HEADER_SIZE || &data[..2] != b"BM" {
+        return Err("not a BMP file".to_owned());
+    }
+    let pixel_offset = read_u32(data, 10) as usize;
+    let width = read_u32(data, 18) as i32;
+    let height = read_u32(data, 22) as i32;
+    let bits_per_pixel = read_u16(data, 28);
+    let compression = read_u32(data, 30);
+    if width <= 0 || height == 0 {
+        return Err(format!("invalid dimensions {} x {}", width, height));
+    }
+    if compression != BI_RGB || (bits_per_pixel != 24 && bits_per_pixel != 32) {
+        return Err(format!("unsupported BMP with {} bits per pixel and compression {}", bits_per_pixel, compression));
+    }
+
+    // rows are stored bottom up unless the height is negative, each padded to a multiple of 4 bytes
+    let bottom_up = height > 0;
+    let height = try!(height.checked_abs().ok_or(format!("invalid height {}", height))) as usize;
+    let width = width as usize;
+    let bytes_per_pixel = bits_per_pixel as usize / 8;
+    // the header can claim any size, so check there's enough data for it before making room for the pixels
+    let stride = try!(width.checked_mul(bytes_per_pixel).and_then(|size| size.checked_add(3))
+        .ok_or(format!("image is too big at {} x {}", width, height))) / 4 * 4;
+    match stride.checked_mul(height).and_then(|size| size.checked_add(pixel_offset)) {
+        Some(end) if end <= data.len() => (),
+        _ => return Err("image data is truncated".to_owned()),
+    }
+
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let row = pixel_offset + stride * if bottom_up { height - 1 - y } else { y };
+        for x in 0..width {
+            let at = row + x * bytes_per_pixel;
+            let alpha = if bytes_per_pixel == 4 { data[at + 3] } else { 0 };
+            pixels.push(RGBA { r: data[at + 2], g: data[at + 1], b: data[at], a: alpha });
+        }
+    }
+
+    let pixels = if pixels.iter().any(|p| p.a != 0) {
+        Pixels::Rgba(pixels)
+    } else {
+        Pixels::Rgb(pixels.iter().map(|p| RGB { r: p.r, g: p.g, b: p.b }).collect())
+    };
+    Ok(Picture { pixels: pixels, width: width, height: height })
+}
+
+/// Encodes the picture as a bottom up BMP: 32 bit if it has transparency, otherwise 24 bit. BMP only has 8 bits per
+/// channel, so 16 bit pictures lose precision.
+pub fn encode(picture: &Picture) -> Vec<u8> {
+    let opaque = |r: u8, g: u8, b: u8| RGBA { r: r, g: g, b: b, a: 255 };
+    let narrow = |v: u16| (v >> 8) as u8;
+    let (pixels, transparent): (Vec<RGBA<u8>>, bool) = match picture.pixels {
+        Pixels::Grey(ref p) => (p.iter().map(|p| opaque(p.0, p.0, p.0)).collect(), false),
+        Pixels::Grey16(ref p) => (p.iter().map(|p| { let v = narrow(p.0); opaque(v, v, v) }).collect(), false),
+        Pixels::GreyAlpha(ref p) => (p.iter().map(|p| RGBA { r: p.0, g: p.0, b: p.0, a: p.1 }).collect(), true),
+        Pixels::GreyAlpha16(ref p) => (p.iter().map(|p| {
+            let v = narrow(p.0);
+            RGBA { r: v, g: v, b: v, a: narrow(p.1) }
+        }).collect(), true),
+        Pixels::Rgb(ref p) => (p.iter().map(|p| opaque(p.r, p.g, p.b)).collect(), false),
+        Pixels::Rgb16(ref p) => (p.iter().map(|p| opaque(narrow(p.r), narrow(p.g), narrow(p.b))).collect(), false),
+        Pixels::Rgba(ref p) => (p.clone(), true),
+        Pixels::Rgba16(ref p) => (p.iter()
+            .map(|p| RGBA { r: narrow(p.r), g: narrow(p.g), b: narrow(p.b), a: narrow(p.a) }).collect(), true),
+    };
+
+    let (width, height) = (picture.width, picture.height);
+    let bytes_per_pixel = if transparent { 4 } else { 3 };
+    let stride = (width * bytes_per_pixel + 3) / 4 * 4;
+    let pixel_offset = FILE_HEADER_SIZE + INFO_HEADER_SIZE;
+    let image_size = stride * height;
+
+    let mut data = Vec::with_capacity(pixel_offset + image_size);
+    data.extend(b"BM".iter().cloned());
+    push_u32(&mut data, (pixel_offset + image_size) as u32);
+    push_u32(&mut data, 0); // reserved
+    push_u32(&mut data, pixel_offset as u32);
+    push_u32(&mut data, INFO_HEADER_SIZE as u32);
+    push_u32(&mut data, width as u32);
+    push_u32(&mut data, height as u32);
+    push_u16(&mut data, 1); // colour planes
+    push_u16(&mut data, bytes_per_pixel as u16 * 8);
+    push_u32(&mut data, BI_RGB);
+    push_u32(&mut data, image_size as u32);
+    push_u32(&mut data, 2835); // 72 DPI, in pixels per metre
+    push_u32(&mut data, 2835);
+    push_u32(&mut data, 0); // palette size
+    push_u32(&mut data, 0); // important colours
+
+    for y in (0..height).rev() {
+        for p in &pixels[(y * width)..((y + 1) * width)] {
+            data.push(p.b);
+            data.push(p.g);
+            data.push(p.r);
+            if transparent {
+                data.push(p.a);
+            }
+        }
+        for _ in (width * bytes_per_pixel)..stride {
+            data.push(0);
+        }
+    }
+    data
+}
+
+fn read_u16(data: &[u8], at: usize) -> u16 {
+    data[at] as u16 | (data[at + 1] as u16) << 8
+}
+
+fn read_u32(data: &[u8], at: usize) -> u32 {
+    read_u16(data, at) as u32 | (read_u16(data, at + 2) as u32) << 16
+}
+
+fn push_u16(data: &mut Vec<u8>, value: u16) {
+    data.push(value as u8);
+    data.push((value >> 8) as u8);
+}
+
+fn push_u32(data: &mut Vec<u8>, value: u32) {
+    push_u16(data, value as u16);
+    push_u16(data, (value >> 16) as u16);
+}
+
+#[cfg(test)]
+mod tests {
+    use lodepng::{Grey, RGB, RGBA};
+    use picture::{Picture, Pixels};
+    use super::{decode, encode};
+
+    #[test]
+    fn round_trips_opaque_images_with_padded_rows() {
+        // 3 x 2 at 3 bytes per pixel pads each row from 9 to 12 bytes
+        let pixels = (0..6).map(|i| RGB { r: i * 40, g: 255 - i, b: i }).collect::<Vec<_>>();
+        let data = encode(&Picture { pixels: Pixels::Rgb(pixels.clone()), width: 3, height: 2 });
+
+        assert_eq!(data.len(), 14 + 40 + 12 * 2);
+        let decoded = decode(&data).unwrap();
+        assert_eq!((decoded.width, decoded.height), (3, 2));
+        match decoded.pixels {
+            Pixels::Rgb(decoded) => assert_eq!(decoded, pixels),
+            _ => panic!("expected opaque pixels"),
+        }
+    }
+
+    #[test]
+    fn keeps_transparency_in_32_bit_images() {
+        let pixels = vec!(RGBA { r: 1, g: 2, b: 3, a: 0 }, RGBA { r: 4, g: 5, b: 6, a: 128 });
+        let decoded = decode(&encode(&Picture { pixels: Pixels::Rgba(pixels.clone()), width: 1, height: 2 })).unwrap();
+
+        match decoded.pixels {
+            Pixels::Rgba(decoded) => assert_eq!(decoded, pixels),
+            _ => panic!("expected transparent pixels"),
+        }
+    }
+
+    #[test]
+    fn writes_grey_images_as_24_bit_colour() {
+        let data = encode(&Picture { pixels: Pixels::Grey(vec!(Grey(7))), width: 1, height: 1 });
+
+        assert_eq!(data[28], 24);
+        assert_eq!(&data[54..], &[7, 7, 7, 0][..]);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(decode(b"P6\n1 1\n255\n\x01\x02\x03").is_err());
+    }
+
+    #[test]
+    fn rejects_headers_claiming_impossible_sizes() {
+        let data = encode(&Picture { pixels: Pixels::Grey(vec!(Grey(7))), width: 1, height: 1 });
+        let with = |at: usize, value: u32| {
+            let mut data = data.clone();
+            let bytes = [value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8];
+            data[at..at + 4].copy_from_slice(&bytes);
+            data
+        };
+
+        assert!(decode(&with(22, 0x8000_0000)).is_err()); // a height of i32::MIN
+        assert!(decode(&with(18, 0x7fff_ffff)).is_err());
+        assert!(decode(&with(10, 0xffff_ffff)).is_err()); // pixels past the end of any file
+        assert!(decode(&with(22, 0)).is_err());
+    }
+}
@@ -1,18 +1,108 @@
+use crossbeam;
+use energy::{colour_distance, BorderPolicy, Energy, EnergyFunction};
+use lazy_remove_seam;
 use lodepng::RGB;
-use std::i32;
+use num_cpus;
+use pixel::Pixel;
+use std::cmp;
+use std::i64;
+use std::sync::{Barrier, Mutex};
 
-// as indicated by the spec, this is the energy of a complete standout pixel, and is also used for pixels on the edge.
-pub const MAX_PIXEL_ENERGY: i32 = 255 * 255 * 3;
+pub use energy::MAX_PIXEL_ENERGY;
+
+/// Energy of pixels which seams should never go through, unless there's no other way.
+pub const PROTECTED_ENERGY: i32 = 1 << 30;
+/// Energy of pixels which seams should go through wherever possible.
+pub const REMOVAL_ENERGY: i32 = -(1 << 30);
+
+/// Finding a seam only uses several threads if each can be given a band of at least this many offsets, since the threads
+/// have to wait for each other after every step.
+pub const MIN_BAND_BREADTH: usize = 2048;
+
+/// How a pixel is marked by the user's masks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mark {
+    Unmarked,
+    Protect,
+    Remove,
+}
+
+/// The direction a seam runs in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    Vertical, // from top to bottom, so removing it reduces the width
+    Horizontal, // from left to right, so removing it reduces the height
+}
+
+/// How the cost of a seam is measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeamCost {
+    /// The total energy of the pixels in the seam, i.e. the energy removed from the image.
+    Backward,
+    /// The total difference between the pixels which become neighbours once the seam is removed, i.e. the energy
+    /// inserted into the image ("Improved Seam Carving for Video Retargeting", Rubinstein, Shamir & Avidan, 2008).
+    /// This avoids the jagged artefacts backward energy can leave behind when removing a seam joins dissimilar pixels.
+    /// The differences are measured directly with `energy::colour_distance`, so the carver's energy function isn't
+    /// used (other than for any mask).
+    Forward,
+}
+
+impl SeamCost {
+    pub fn from_name(name: &str) -> Option<SeamCost> {
+        match name {
+            "backward" => Some(SeamCost::Backward),
+            "forward" => Some(SeamCost::Forward),
+            _ => None,
+        }
+    }
+}
+
+/// How a carver calculates energy and measures seams.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CarverOptions {
+    pub seam_cost: SeamCost,
+    pub energy: Energy,
+    pub border: BorderPolicy,
+    pub threads: usize, // the most threads to use when finding seams in wide enough images
+}
+
+impl Default for CarverOptions {
+    /// As described in the spec, using every CPU.
+    fn default() -> CarverOptions {
+        CarverOptions {
+            seam_cost: SeamCost::Backward,
+            energy: Energy::DualGradient,
+            border: BorderPolicy::MaxEnergy,
+            threads: num_cpus::get(),
+        }
+    }
+}
 
 /// To avoid repeated allocations, 1 carver can be created and reused indefinitely for the same image.
 pub struct Carver {
     pub energy: Vec<i32>, // energy of each pixel
-    dist_to: Vec<i32>, // should be ok recording distances as i32 as long as path is less than 20,000 pixels long
+    seam_cost: SeamCost,
+    energy_function: Box<EnergyFunction>,
+    border: BorderPolicy,
+    threads: usize,
+    pixels: Vec<RGB<u8>>, // 8 bit RGB copy of the image, which shrinks along with it as seams are removed
+    mask: Vec<Mark>, // empty if there's no mask, otherwise shrinks along with the image as seams are removed
+    guide: Vec<usize>, // the offset to stay near in each step (empty if there's no guide, see `set_guide`)
+    guide_cost: i64,
+    dist_to: Vec<i64>, // i64 so that paths through several protected pixels can't overflow
     prev_vertex: Vec<usize>, // records the path back in terms of vertices rather than edges (edge_to)
+    last_seam_cost: i64,
+    searched: Option<(Orientation, usize, usize)>, // orientation, width and height of the image dist_to is for
+    valid_offsets: Vec<usize>, // for each step, how many of its first offsets still have correct dist_to/prev_vertex
+    bands: Vec<Band>, // each thread's working space when searching for a seam, kept between searches
 }
 
 impl Carver {
     pub fn new(num_pixels: usize) -> Carver {
+        Carver::with_options(num_pixels, CarverOptions::default())
+    }
+
+    pub fn with_options(num_pixels: usize, options: CarverOptions) -> Carver {
         // We have an implicit graph where we have:
         // - a fake source pixel which has an edge to every pixel in the first row of the image
         // - each pixel in the image has an edge to the pixel below and the pixel to the left and right of that
@@ -21,125 +111,551 @@ impl Carver {
         let vertex_count = num_pixels + 2;
         Carver {
             energy: vec![0; num_pixels],
-            dist_to: vec![i32::max_value(); vertex_count],
+            seam_cost: options.seam_cost,
+            energy_function: options.energy.function(),
+            border: options.border,
+            threads: cmp::max(options.threads, 1),
+            pixels: Vec::new(),
+            mask: Vec::new(),
+            guide: Vec::new(),
+            guide_cost: 0,
+            dist_to: vec![i64::max_value(); vertex_count],
             prev_vertex: vec![0; vertex_count],
+            last_seam_cost: 0,
+            searched: None,
+            valid_offsets: Vec::new(),
+            bands: Vec::new(),
+        }
+    }
+
+    /// Makes room for images of up to `num_pixels` pixels, so a carver can be reused for a bigger image than it was
+    /// created for (e.g. by a worker carving many images) without reallocating for every image.
+    pub fn reserve(&mut self, num_pixels: usize) {
+        let vertex_count = num_pixels + 2;
+        if self.dist_to.len() < vertex_count {
+            self.dist_to.resize(vertex_count, i64::max_value());
+            self.prev_vertex.resize(vertex_count, 0);
         }
     }
 
     fn assert_capacity_matches_image_dimensions(&self, width: usize, height: usize) {
-        assert!(width * height <= self.energy.len(), "carver must have been initialised with enough size for given pixels");
+        assert!(width * height + 2 <= self.dist_to.len(),
+            "carver must have been initialised with enough size for given pixels");
     }
 
-    pub fn calculate_energy(&mut self, width: usize, height: usize, pixels: &[RGB<u8>]) {
+    /// Calculates the energy of every pixel of the `width` x `height` image, keeping an 8 bit RGB copy of it which
+    /// `remove_seam` then keeps up to date.
+    pub fn calculate_energy<P: Pixel>(&mut self, width: usize, height: usize, pixels: &[P]) {
         let num_pixels = width * height;
-        self.energy.truncate(num_pixels);
         self.assert_capacity_matches_image_dimensions(width, height);
         assert!(num_pixels <= pixels.len(), "width * height must be <= given pixel slice");
+        self.pixels.clear();
+        self.pixels.extend(pixels[..num_pixels].iter().map(|pixel| pixel.to_rgb8()));
+        self.recalculate_energy(width, height);
+    }
 
+    fn recalculate_energy(&mut self, width: usize, height: usize) {
+        let num_pixels = width * height;
+        self.energy.resize(num_pixels, 0); // the same carver may be used for differently sized images
 
-        for i in 0..num_pixels {
-            let curr_modulo_width = i % width;
-            let energy = if i < width { // first row
-                MAX_PIXEL_ENERGY
-            } else if i > width * (height - 1) { // last row
-                MAX_PIXEL_ENERGY
-            } else if curr_modulo_width == 0 { // first column
-                MAX_PIXEL_ENERGY
-            } else if curr_modulo_width == width - 1 { // last column
-                MAX_PIXEL_ENERGY
-            } else {
-                let energy_x = {
-                    let x1 = pixels[i - 1];
-                    let x2 = pixels[i + 1];
-                    (x1.r as i32 - x2.r as i32).pow(2) + (x1.g as i32 - x2.g as i32).pow(2) + (x1.b as i32 - x2.b  as i32).pow(2)
-                };
+        if !self.mask.is_empty() {
+            assert!(num_pixels <= self.mask.len(), "mask must be at least as big as the image");
+        }
+
+        self.energy_function.prepare(&self.pixels, width, height);
+        for pixel in 0..num_pixels {
+            self.update_energy_of(width, height, pixel);
+        }
+        self.searched = None;
+    }
+
+    fn update_energy_of(&mut self, width: usize, height: usize, pixel: usize) {
+        let (x, y) = (pixel % width, pixel / width);
+        let border = self.border;
+        let on_edge = x == 0 || y == 0 || x == width - 1 || y == height - 1;
+        let energy = match self.mask.get(pixel).and_then(|&mark| mask_energy(mark)) {
+            Some(energy) => energy,
+            None if on_edge && border == BorderPolicy::MaxEnergy => self.energy_function.max_energy(),
+            None => {
+                let pixels = &self.pixels;
+                let pixel_at = |x: isize, y: isize|
+                    pixels[border.resolve(y, height) * width + border.resolve(x, width)];
+                self.energy_function.energy(&pixel_at, x as isize, y as isize)
+            },
+        };
+        self.energy[pixel] = energy;
+    }
+
+    /// Updates the carver after `seam` has been removed from the `width` x `height` image. Its copy of the image, the
+    /// mask, energy and seam search state are shifted along with the pixels, then only the energy of pixels near the
+    /// seam is recalculated (unless the energy function looks at the whole image), and the next search in the same
+    /// orientation only revisits the parts of the image whose distances could have changed. Removing an empty seam (as
+    /// found in an empty image) does nothing.
+    pub fn remove_seam(&mut self, width: usize, height: usize, orientation: Orientation, seam: &Vec<usize>) {
+        if seam.is_empty() {
+            return;
+        }
+        let (new_width, new_height) = lazy_remove_seam(&mut self.energy[..], width, height, orientation, seam);
+        self.energy.truncate(new_width * new_height);
+        if !self.mask.is_empty() {
+            lazy_remove_seam(&mut self.mask[..], width, height, orientation, seam);
+        }
+        lazy_remove_seam(&mut self.dist_to[..], width, height, orientation, seam);
+        lazy_remove_seam(&mut self.prev_vertex[..], width, height, orientation, seam);
+        lazy_remove_seam(&mut self.pixels[..], width, height, orientation, seam);
+        self.pixels.truncate(new_width * new_height);
+
+        let radius = match self.energy_function.radius() {
+            Some(radius) => radius,
+            None => {
+                self.recalculate_energy(new_width, new_height);
+                return;
+            },
+        };
 
-                let energy_y = {
-                    let y1 = pixels[i - width];
-                    let y2 = pixels[i + width];
-                    (y1.r as i32 - y2.r as i32).pow(2) + (y1.g as i32 - y2.g as i32).pow(2) + (y1.b as i32 - y2.b as i32).pow(2)
+        let (length, breadth) = match orientation {
+            Orientation::Vertical => (new_height, new_width),
+            Orientation::Horizontal => (new_width, new_height),
+        };
+        let old_offset_of = |pixel: usize| match orientation {
+            Orientation::Vertical => pixel % width,
+            Orientation::Horizontal => pixel / width,
+        };
+        let new_pixel_at = |step: usize, offset: usize| match orientation {
+            Orientation::Vertical => step * new_width + offset,
+            Orientation::Horizontal => offset * new_width + step,
+        };
+        let seam_offsets = seam.iter().map(|&pixel| old_offset_of(pixel)).collect::<Vec<_>>();
+
+        // anything which looks up to `r` pixels away may have changed within `r` pixels of the seam, in any of the `r`
+        // steps either side (as found by the border policy, so the last step may be near the first), or anywhere in the
+        // step if it might wrap around to the seam
+        let border = self.border;
+        let changed = |r: usize, step: usize| {
+            let (mut min, mut max) = (breadth, 0);
+            for d in 0..(2 * r + 1) {
+                let offset = seam_offsets[border.resolve(step as isize + d as isize - r as isize, length)];
+                min = cmp::min(min, offset);
+                max = cmp::max(max, offset);
+            }
+            let (from, to) = (min.saturating_sub(r), cmp::min(max + r, breadth));
+            if border == BorderPolicy::Wrapped && (from < r || to + r > breadth) { (0, breadth) } else { (from, to) }
+        };
+
+        for step in 0..length {
+            let (from, to) = changed(radius, step);
+            for offset in from..to {
+                self.update_energy_of(new_width, new_height, new_pixel_at(step, offset));
+            }
+        }
+
+        // the distance to a pixel depends on the cost of getting there (which looks 1 pixel away with forward energy)
+        // and the distances to the pixels before it, so pixels further from the seam with each step are affected
+        if self.searched == Some((orientation, width, height)) {
+            for step in 0..length {
+                let from = changed(cmp::max(radius, 1), step).0;
+                let valid = if step == 0 {
+                    cmp::min(from, self.valid_offsets[step])
+                } else {
+                    cmp::min(cmp::min(from, self.valid_offsets[step]), self.valid_offsets[step - 1].saturating_sub(1))
                 };
+                self.valid_offsets[step] = valid;
+                if step > 0 {
+                    for offset in 0..valid {
+                        let pixel = new_pixel_at(step, offset);
+                        self.prev_vertex[pixel] = new_pixel_at(step - 1, old_offset_of(self.prev_vertex[pixel]));
+                    }
+                }
+            }
+            self.searched = Some((orientation, new_width, new_height));
+        } else {
+            self.searched = None;
+        }
+    }
 
-                (energy_x + energy_y)
-            };
+    /// Marks pixels of the image (laid out like its pixels) to be protected from or targeted by seams. The mask must be
+    /// set before energy is calculated, and kept in step with the image using `remove_seam`.
+    pub fn set_mask(&mut self, mask: Vec<Mark>) {
+        self.mask = mask;
+    }
 
-            self.energy[i] = energy;
+    /// Makes seams cost `cost` more for each pixel they stray, in each step, from the offset `offsets` gives for that
+    /// step, so they stay near an earlier seam (e.g. one removed from the previous frame of a video). An empty guide
+    /// turns this off. Distances from the last search are only thrown away if the guide changes.
+    pub fn set_guide(&mut self, offsets: Vec<usize>, cost: i32) {
+        if offsets != self.guide || cost as i64 != self.guide_cost {
+            self.searched = None;
         }
+        self.guide = offsets;
+        self.guide_cost = cost as i64;
     }
 
+    /// The indexes of the pixels of the image which are still marked for removal.
+    pub fn marked_for_removal(&self, width: usize, height: usize) -> Vec<usize> {
+        self.mask.iter().take(width * height).enumerate()
+            .filter(|&(_, &mark)| mark == Mark::Remove)
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Finds the lowest energy vertical seam, returned as the index of 1 pixel per row (from top to bottom).
     pub fn find_seam(&mut self, width: usize, height: usize) -> Vec<usize> {
+        self.find_oriented_seam(width, height, Orientation::Vertical)
+    }
+
+    /// Finds the lowest energy horizontal seam, returned as the index of 1 pixel per column (from left to right).
+    pub fn find_horizontal_seam(&mut self, width: usize, height: usize) -> Vec<usize> {
+        self.find_oriented_seam(width, height, Orientation::Horizontal)
+    }
+
+    /// Rather than transposing the image to find horizontal seams, we walk the image in "steps" along the seam
+    /// (rows for a vertical seam, columns for a horizontal one) and "offsets" across it, mapping each to a pixel index.
+    ///
+    /// The distances to the pixels in each step only depend on those in the previous step, so wide images are split into
+    /// bands of offsets which are searched in parallel, with each thread only needing the distances at the edges of its
+    /// neighbours' bands before moving on to the next step.
+    ///
+    /// An empty image has no seams, so gives an empty one.
+    pub fn find_oriented_seam(&mut self, width: usize, height: usize, orientation: Orientation) -> Vec<usize> {
         self.assert_capacity_matches_image_dimensions(width, height);
+        if width == 0 || height == 0 {
+            return Vec::new();
+        }
 
         let num_pixels = width * height;
         let fake_src = num_pixels;
         let fake_dest = num_pixels + 1;
+        let (length, breadth) = match orientation {
+            Orientation::Vertical => (height, width),
+            Orientation::Horizontal => (width, height),
+        };
+        let layout = Layout { orientation: orientation, width: width };
 
-        for i in 0..(num_pixels + 2) {
-            self.dist_to[i] = i32::max_value();
-            self.prev_vertex[i] = 0;
-        }
+        // distances from the last search are reused if only the pixels after `valid_offsets` have changed since
+        let reusable = self.searched == Some((orientation, width, height));
 
-        // fake source pixel edges to each pixel in the first row
-        for pixel in 0..width {
-            self.dist_to[pixel] = self.energy[pixel];
-            self.prev_vertex[pixel] = fake_src;
+        let threads = cmp::max(1, cmp::min(self.threads, breadth / MIN_BAND_BREADTH));
+        if self.bands.len() < threads {
+            self.bands.resize(threads, Band::default());
         }
+        {
+            let costs = Costs {
+                seam_cost: self.seam_cost,
+                energy: &self.energy,
+                pixels: &self.pixels,
+                mask: &self.mask,
+                guide: &self.guide,
+                guide_cost: self.guide_cost,
+                layout: layout,
+                breadth: breadth,
+            };
+            let search = BandSearch {
+                costs: &costs,
+                length: length,
+                previous: &self.dist_to,
+                valid_offsets: if reusable { &self.valid_offsets[..] } else { &[] },
+            };
 
-        // each pixel in the image has an edge to the pixel below and the pixel to the left and right of that
-        for pixel in 0..(num_pixels - width) {
-            let tmp_len_2;
-            let tmp_len_3;
-            let curr_modulo_width = pixel % width;
-            let next_pixel_options: &[usize] = if curr_modulo_width == 0 { // first column
-                tmp_len_2 = [pixel + width, pixel + width + 1];
-                &tmp_len_2
-            } else if curr_modulo_width == width - 1 { // last column
-                tmp_len_2 = [pixel + width - 1, pixel + width];
-                &tmp_len_2
+            let bounds = (0..(threads + 1)).map(|t| t * breadth / threads).collect::<Vec<_>>();
+            let edges = (0..threads).map(|_| [Mutex::new((0, 0)), Mutex::new((0, 0))]).collect::<Vec<_>>();
+            let barrier = Barrier::new(threads);
+            let bands = &mut self.bands[..threads];
+            if threads == 1 {
+                search.search_band(&mut bands[0], 0, breadth, 0, &edges, &barrier);
             } else {
-                tmp_len_3 = [pixel + width - 1, pixel + width, pixel + width + 1];
-                &tmp_len_3
-            };
+                let (search, bounds, edges, barrier) = (&search, &bounds, &edges, &barrier);
+                crossbeam::scope(|scope| {
+                    let handles = bands.iter_mut().enumerate().map(|(t, band)| scope.spawn(move ||
+                        search.search_band(band, bounds[t], bounds[t + 1], t, edges, barrier)
+                    )).collect::<Vec<_>>();
+                    for handle in handles {
+                        handle.join();
+                    }
+                });
+            }
+        }
 
-            for &pixel_option in next_pixel_options {
-                if self.dist_to[pixel_option] > self.dist_to[pixel] + self.energy[pixel_option] {
-                    self.dist_to[pixel_option] = self.dist_to[pixel] + self.energy[pixel_option];
-                    self.prev_vertex[pixel_option] = pixel;
+        // copy the new distances back into the graph; each pixel has an edge from the pixel in the previous step and
+        // the pixels either side of that, and the fake source pixel has an edge to each pixel in the first step
+        for band in self.bands[..threads].iter() {
+            let band_breadth = band.to - band.from;
+            for step in 0..length {
+                let first_changed = if reusable { self.valid_offsets[step] } else { 0 };
+                for offset in cmp::max(first_changed, band.from)..band.to {
+                    let i = step * band_breadth + offset - band.from;
+                    let pixel = layout.pixel_at(step, offset);
+                    self.dist_to[pixel] = band.dist_to[i];
+                    self.prev_vertex[pixel] = if step == 0 {
+                        fake_src
+                    } else {
+                        layout.pixel_at(step - 1, offset + band.moves[i] as usize - 1)
+                    };
                 }
             }
         }
 
-        // each pixel in the image has an edge to the pixel below and the pixel to the left and right of that
-        for pixel in (num_pixels - width)..num_pixels {
+        // each pixel in the last step has an edge to the fake destination pixel
+        self.dist_to[fake_dest] = i64::max_value();
+        for offset in 0..breadth {
+            let pixel = layout.pixel_at(length - 1, offset);
             if self.dist_to[fake_dest] > self.dist_to[pixel] {
                 self.dist_to[fake_dest] = self.dist_to[pixel];
                 self.prev_vertex[fake_dest] = pixel;
             }
         }
+        self.searched = Some((orientation, width, height));
+        self.valid_offsets.clear();
+        self.valid_offsets.resize(length, breadth);
 
-        let mut curr = fake_dest;
-        let mut path = Vec::with_capacity(height);
-        while curr != fake_src {
-            if curr != fake_dest {
-                path.push(curr);
-            }
-            curr = self.prev_vertex[curr]
+        // follow the path back 1 pixel per step (pixels in the first step may not point at the current fake source)
+        self.last_seam_cost = self.dist_to[fake_dest];
+        let mut curr = self.prev_vertex[fake_dest];
+        let mut path = Vec::with_capacity(length);
+        for _ in 0..length {
+            path.push(curr);
+            curr = self.prev_vertex[curr];
         }
         path.reverse();
         path
     }
+
+    /// The total cost (as measured by this carver's `SeamCost`) of the seam which was found most recently.
+    pub fn last_seam_cost(&self) -> i64 {
+        self.last_seam_cost
+    }
+
+    /// The cost of the cheapest path to each pixel from the start of the image (laid out like its pixels), as found by
+    /// the most recent seam search, unless the image has changed since.
+    pub fn distances(&self) -> Option<&[i64]> {
+        match self.searched {
+            Some((orientation, width, height)) => {
+                let breadth = match orientation {
+                    Orientation::Vertical => width,
+                    Orientation::Horizontal => height,
+                };
+                if self.valid_offsets.iter().all(|&valid| valid == breadth) {
+                    Some(&self.dist_to[..(width * height)])
+                } else {
+                    None
+                }
+            },
+            None => None,
+        }
+    }
+}
+
+/// Maps steps and offsets (see `Carver::find_oriented_seam`) to pixel indexes.
+#[derive(Clone, Copy)]
+struct Layout {
+    orientation: Orientation,
+    width: usize,
+}
+
+impl Layout {
+    fn pixel_at(&self, step: usize, offset: usize) -> usize {
+        match self.orientation {
+            Orientation::Vertical => step * self.width + offset,
+            Orientation::Horizontal => offset * self.width + step,
+        }
+    }
+}
+
+/// Everything needed to work out the cost of each edge of the graph, borrowed from a carver so it can be shared
+/// between threads.
+struct Costs<'a> {
+    seam_cost: SeamCost,
+    energy: &'a [i32],
+    pixels: &'a [RGB<u8>],
+    mask: &'a [Mark],
+    guide: &'a [usize],
+    guide_cost: i64,
+    layout: Layout,
+    breadth: usize,
+}
+
+impl<'a> Costs<'a> {
+    /// The cost of the seam going through the pixel at (`step`, `offset`) from `prev_offset` in the previous step.
+    fn edge_cost(&self, step: usize, prev_offset: usize, offset: usize) -> i64 {
+        let pixel = self.layout.pixel_at(step, offset);
+        let cost = match self.seam_cost {
+            SeamCost::Backward => self.energy[pixel] as i64, // already includes the mask
+            SeamCost::Forward => {
+                let mask = self.mask.get(pixel).and_then(|&mark| mask_energy(mark)).unwrap_or(0);
+                let pixel_at = |step: usize, offset: usize| self.layout.pixel_at(step, offset);
+                forward_cost(self.pixels, &pixel_at, self.breadth, step, prev_offset, offset) as i64 + mask as i64
+            },
+        };
+        let straying = match self.guide.get(step) {
+            Some(&guided) => self.guide_cost * (cmp::max(offset, guided) - cmp::min(offset, guided)) as i64,
+            None => 0,
+        };
+        cost + straying
+    }
+}
+
+/// The distances to the pixels in a band of offsets (`from..to`) of every step, laid out step by step. The vectors are
+/// only ever grown, so a carver's bands can be reused for search after search without reallocating.
+#[derive(Clone, Default)]
+struct Band {
+    from: usize,
+    to: usize,
+    dist_to: Vec<i64>,
+    moves: Vec<u8>, // offset of the previous pixel on the path relative to `offset - 1`, i.e. 0, 1 or 2
+    previous: Vec<i64>, // the distances in the previous step, while searching (see `BandSearch::search_band`)
+}
+
+/// A search for the distances to every pixel, which can be split between several threads.
+struct BandSearch<'a> {
+    costs: &'a Costs<'a>,
+    length: usize,
+    previous: &'a [i64], // distances from the last search, for offsets before `valid_offsets`
+    valid_offsets: &'a [usize],
+}
+
+impl<'a> BandSearch<'a> {
+    /// Finds the distances in the band of offsets from `from` to `to` into `band`, which is band number `band_index`
+    /// of `edges.len()`. After each step, the first and last distances in the band are shared through `edges`
+    /// (alternating between 2 slots, so neighbours can't overwrite a step's distances before they've been read), and
+    /// all threads wait at `barrier` before reading their neighbours' distances.
+    fn search_band(&self, band: &mut Band, from: usize, to: usize, band_index: usize,
+            edges: &[[Mutex<(i64, i64)>; 2]], barrier: &Barrier) {
+        let costs = self.costs;
+        let band_breadth = to - from;
+        band.from = from;
+        band.to = to;
+        // every distance is written before it's read, as is every move which is read (moves into the first step, and
+        // into offsets reused from the last search, never are)
+        band.dist_to.resize(self.length * band_breadth, 0);
+        band.moves.resize(self.length * band_breadth, 1);
+
+        // the previous step's distances for this band, with the closest distance in the neighbouring band either side
+        // (or i64::MAX past the edge of the image, so it's never the cheapest)
+        let previous = &mut band.previous;
+        previous.clear();
+        previous.resize(band_breadth + 2, i64::max_value());
+
+        for step in 0..self.length {
+            let first_changed = self.valid_offsets.get(step).cloned().unwrap_or(0);
+            {
+                let row = &mut band.dist_to[(step * band_breadth)..((step + 1) * band_breadth)];
+                let moves = &mut band.moves[(step * band_breadth)..((step + 1) * band_breadth)];
+                for (i, offset) in (from..to).enumerate() {
+                    if offset < first_changed {
+                        row[i] = self.previous[costs.layout.pixel_at(step, offset)];
+                    } else if step == 0 {
+                        row[i] = costs.edge_cost(step, offset, offset);
+                    } else {
+                        let (dist, best_move) = match costs.seam_cost {
+                            SeamCost::Backward => {
+                                let (prev_dist, best_move) = cheapest(previous[i], previous[i + 1], previous[i + 2]);
+                                (prev_dist + costs.edge_cost(step, offset, offset), best_move)
+                            },
+                            SeamCost::Forward => {
+                                let via = |m: usize| if previous[i + m] == i64::max_value() {
+                                    i64::max_value()
+                                } else {
+                                    previous[i + m] + costs.edge_cost(step, offset + m - 1, offset)
+                                };
+                                cheapest(via(0), via(1), via(2))
+                            },
+                        };
+                        row[i] = dist;
+                        moves[i] = best_move;
+                    }
+                }
+
+                *edges[band_index][step % 2].lock().unwrap() = (row[0], row[band_breadth - 1]);
+                for (prev, &dist) in previous[1..].iter_mut().zip(row.iter()) {
+                    *prev = dist;
+                }
+            }
+
+            barrier.wait();
+            if band_index > 0 {
+                previous[0] = edges[band_index - 1][step % 2].lock().unwrap().1;
+            }
+            if band_index + 1 < edges.len() {
+                previous[band_breadth + 1] = edges[band_index + 1][step % 2].lock().unwrap().0;
+            }
+        }
+    }
+}
+
+/// The smallest of the distances via the previous step's pixels at `offset - 1`, `offset` and `offset + 1`, along with
+/// which of them it was (preferring the earliest, so ties are broken the same way however the image is split into
+/// bands). Simple enough to be compiled into conditional moves rather than branches.
+fn cheapest(left: i64, middle: i64, right: i64) -> (i64, u8) {
+    if left <= middle && left <= right {
+        (left, 0)
+    } else if middle <= right {
+        (middle, 1)
+    } else {
+        (right, 2)
+    }
+}
+
+fn mask_energy(mark: Mark) -> Option<i32> {
+    match mark {
+        Mark::Unmarked => None,
+        Mark::Protect => Some(PROTECTED_ENERGY),
+        Mark::Remove => Some(REMOVAL_ENERGY),
+    }
+}
+
+/// The forward energy cost of the seam passing through the pixel at (`step`, `offset`) having come from the pixel at
+/// `prev_offset` in the previous step: removing the pixel makes its 2 neighbours across the seam adjacent, and moving
+/// diagonally also makes the pixel behind it adjacent to one of those neighbours. Neighbours past the edge of the image
+/// are treated as being the pixel itself.
+fn forward_cost(pixels: &[RGB<u8>], pixel_at: &Fn(usize, usize) -> usize, breadth: usize, step: usize,
+        prev_offset: usize, offset: usize) -> i32 {
+    let before = pixels[pixel_at(step, if offset == 0 { 0 } else { offset - 1 })];
+    let after = pixels[pixel_at(step, cmp::min(offset + 1, breadth - 1))];
+    let newly_adjacent = colour_distance(before, after);
+
+    if prev_offset == offset || step == 0 {
+        newly_adjacent
+    } else {
+        let behind = pixels[pixel_at(step - 1, offset)];
+        if prev_offset < offset {
+            newly_adjacent + colour_distance(behind, before)
+        } else {
+            newly_adjacent + colour_distance(behind, after)
+        }
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
-    use lodepng::RGB;
-    use super::{Carver, MAX_PIXEL_ENERGY};
+    use energy::{BorderPolicy, Energy};
+    use lodepng::{Grey, RGB, RGBA};
+    use {lazy_remove_seam, noisy_image};
+    use super::{Carver, CarverOptions, Mark, Orientation, SeamCost, MAX_PIXEL_ENERGY, MIN_BAND_BREADTH};
+    #[cfg(feature = "bench")] use test::Bencher;
 
     fn rgb(r: u8, g: u8, b: u8) -> RGB<u8> {
         RGB { r: r, g: g, b: b }
     }
 
+    /// Removes `count` vertical seams from the image, then returns the image's new width.
+    #[cfg(feature = "bench")]
+    fn remove_seams(carver: &mut Carver, pixels: &mut Vec<RGB<u8>>, width: usize, height: usize, count: usize,
+            incrementally: bool) -> usize {
+        carver.calculate_energy(width, height, pixels);
+        let mut width = width;
+        for _ in 0..count {
+            let seam = carver.find_seam(width, height);
+            lazy_remove_seam(&mut pixels[..], width, height, Orientation::Vertical, &seam);
+            if incrementally {
+                carver.remove_seam(width, height, Orientation::Vertical, &seam);
+            } else {
+                carver.calculate_energy(width - 1, height, pixels);
+            }
+            width -= 1;
+        }
+        width
+    }
+
     #[test]
     fn calculates_energy_as_given_in_example_in_spec() {
         let mut carver = Carver::new(3 * 4);
@@ -158,6 +674,40 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn calculates_energy_of_border_pixels_from_mirrored_or_wrapped_neighbours() {
+        // grey levels of a 3 x 1 image: 0 100 200
+        let image = [0, 100, 200].iter().map(|&v| rgb(v, v, v)).collect::<Vec<_>>();
+        let with_border = |border| {
+            let mut carver = Carver::with_options(3, CarverOptions { border: border, ..CarverOptions::default() });
+            carver.calculate_energy(3, 1, &image);
+            carver.energy
+        };
+
+        // vertically, every pixel's neighbours are itself; horizontally, mirroring makes the edge pixels' neighbours
+        // both the middle pixel, while wrapping makes them the middle and the opposite edge
+        assert_eq!(with_border(BorderPolicy::Mirrored), vec!(0, 3 * 200 * 200, 0));
+        assert_eq!(with_border(BorderPolicy::Wrapped), vec!(3 * 100 * 100, 3 * 200 * 200, 3 * 100 * 100));
+        assert_eq!(with_border(BorderPolicy::MaxEnergy), vec!(MAX_PIXEL_ENERGY; 3));
+    }
+
+    #[test]
+    fn calculates_energy_of_transparent_and_grey_pixels_from_their_colour_against_black() {
+        let options = CarverOptions { border: BorderPolicy::Wrapped, ..CarverOptions::default() };
+        let mut carver = Carver::with_options(3, options);
+
+        // fully transparent pixels count as black, so the opaque middle pixel stands out like in the grey image
+        carver.calculate_energy(3, 1, &[
+            RGBA { r: 200, g: 200, b: 200, a: 0 }, RGBA { r: 100u8, g: 100, b: 100, a: 255 },
+            RGBA { r: 50, g: 50, b: 50, a: 0 },
+        ]);
+        let transparent = carver.energy.clone();
+        carver.calculate_energy(3, 1, &[Grey(0u16), Grey(100 * 257), Grey(0)]);
+
+        assert_eq!(transparent, vec!(3 * 100 * 100, 0, 3 * 100 * 100));
+        assert_eq!(carver.energy, transparent);
+    }
+
     #[test]
     fn finds_seam_as_given_in_example_in_spec() {
         let img_width = 6;
@@ -181,4 +731,261 @@ mod tests {
         // --  --  26  --  --  --
         assert_eq!(seam, vec!(2, 9, 15, 21, 26));
     }
+
+    #[test]
+    fn finds_no_seam_in_an_empty_image() {
+        for &(width, height) in [(0, 0), (0, 3), (3, 0)].iter() {
+            for &orientation in [Orientation::Vertical, Orientation::Horizontal].iter() {
+                let mut carver = Carver::new(0);
+                carver.calculate_energy(width, height, &[rgb(0, 0, 0); 0]);
+
+                let seam = carver.find_oriented_seam(width, height, orientation);
+                carver.remove_seam(width, height, orientation, &seam);
+
+                assert_eq!(seam, vec!());
+            }
+        }
+    }
+
+    #[test]
+    fn only_gives_distances_while_they_match_the_image() {
+        let image = noisy_image(5, 4);
+        let mut carver = Carver::new(5 * 4);
+        carver.calculate_energy(5, 4, &image);
+        assert!(carver.distances().is_none());
+
+        let seam = carver.find_seam(5, 4);
+        {
+            let distances = carver.distances().expect("just searched");
+            assert_eq!(distances.len(), 5 * 4);
+            // paths only get more expensive further down the image
+            let cheapest_in_row = |y: usize| *distances[(y * 5)..((y + 1) * 5)].iter().min().unwrap();
+            assert!((1..4).all(|y| cheapest_in_row(y) >= cheapest_in_row(y - 1)));
+        }
+
+        carver.remove_seam(5, 4, Orientation::Vertical, &seam);
+        assert!(carver.distances().is_none());
+    }
+
+    #[test]
+    fn finds_horizontal_seam_in_transposed_example_in_spec() {
+        // same energies as finds_seam_as_given_in_example_in_spec, but transposed
+        let img_width = 5;
+        let img_height = 6;
+        let mut carver = Carver::new(img_width * img_height);
+        carver.energy = vec!(
+            MAX_PIXEL_ENERGY, MAX_PIXEL_ENERGY, MAX_PIXEL_ENERGY, MAX_PIXEL_ENERGY, MAX_PIXEL_ENERGY,
+            MAX_PIXEL_ENERGY, 23346,            47908,            31400,            MAX_PIXEL_ENERGY,
+            MAX_PIXEL_ENERGY, 51304,            61346,            37927,            MAX_PIXEL_ENERGY,
+            MAX_PIXEL_ENERGY, 31519,            35919,            14437,            MAX_PIXEL_ENERGY,
+            MAX_PIXEL_ENERGY, 55112,            38887,            63076,            MAX_PIXEL_ENERGY,
+            MAX_PIXEL_ENERGY, MAX_PIXEL_ENERGY, MAX_PIXEL_ENERGY, MAX_PIXEL_ENERGY, MAX_PIXEL_ENERGY,
+        );
+
+        let seam = carver.find_horizontal_seam(img_width, img_height);
+
+        // expecting a seam of MAX_PIXEL_ENERGY, 31519, 35919, 14437, MAX_PIXEL_ENERGY in the following pattern:
+        // --  --  --  --  --
+        // --  --  --  --  --
+        // 10  --  --  --  14
+        // --  16  17  18  --
+        // --  --  --  --  --
+        // --  --  --  --  --
+        assert_eq!(seam, vec!(10, 16, 17, 18, 14));
+    }
+
+    #[test]
+    fn finds_different_seams_with_backward_and_forward_energy() {
+        // grey levels of a 4 x 3 image:
+        // 0    0    0    100
+        // 100  0    0    0
+        // 200  100  0    200
+        let image = [0, 0, 0, 100, 100, 0, 0, 0, 200, 100, 0, 200].iter()
+            .map(|&v| rgb(v, v, v)).collect::<Vec<_>>();
+
+        let mut backward = Carver::new(4 * 3);
+        backward.calculate_energy(4, 3, &image);
+        let mut forward = Carver::with_options(4 * 3,
+            CarverOptions { seam_cost: SeamCost::Forward, ..CarverOptions::default() });
+        forward.calculate_energy(4, 3, &image);
+
+        // backward energy of the middle row is MAX, 60000, 0, MAX so the seam goes through pixel 6, then ties between
+        // 9 and 10 (both MAX) go to the first:
+        // --  1   --  --
+        // --  --  6   --
+        // --  9   --  --
+        assert_eq!(backward.find_seam(4, 3), vec!(1, 6, 9));
+        assert_eq!(backward.last_seam_cost(), MAX_PIXEL_ENERGY as i64 * 2);
+
+        // with forward energy, removing 9 would cost 120000 (200 at 8 next to 0 at 10), so the seam removes 10 instead
+        // which only costs 30000 (100 at 9 next to 200 at 11); pixels 1 and 6 cost nothing as they're between 0s:
+        // --  1   --  --
+        // --  --  6   --
+        // --  --  10  --
+        assert_eq!(forward.find_seam(4, 3), vec!(1, 6, 10));
+        assert_eq!(forward.last_seam_cost(), 30000);
+    }
+
+    #[test]
+    fn avoids_protected_pixels_and_seeks_pixels_marked_for_removal() {
+        // a flat 4 x 3 image has 0 energy everywhere but the border, so the middle row has 2 cheap pixels (5 and 6) and
+        // ties go to the first
+        let image = vec![rgb(50, 50, 50); 4 * 3];
+        let seam_with_mask = |mask: Vec<Mark>| {
+            let mut carver = Carver::new(4 * 3);
+            carver.set_mask(mask);
+            carver.calculate_energy(4, 3, &image);
+            carver.find_seam(4, 3)
+        };
+        let mark = |pixel: usize, mark: Mark| {
+            let mut mask = vec![Mark::Unmarked; 4 * 3];
+            mask[pixel] = mark;
+            mask
+        };
+
+        assert_eq!(seam_with_mask(Vec::new()), vec!(0, 5, 8));
+        assert_eq!(seam_with_mask(mark(5, Mark::Protect)), vec!(1, 6, 9));
+        assert_eq!(seam_with_mask(mark(6, Mark::Remove)), vec!(1, 6, 9));
+    }
+
+    #[test]
+    fn stays_near_the_guide() {
+        // as above, the seam would go through 0, 5 and 8, but going through the guide's column costs nothing extra
+        let image = vec![rgb(50, 50, 50); 4 * 3];
+        let mut carver = Carver::new(4 * 3);
+        carver.calculate_energy(4, 3, &image);
+
+        carver.set_guide(vec!(2, 2, 2), 1);
+        assert_eq!(carver.find_seam(4, 3), vec!(2, 6, 10));
+
+        carver.set_guide(Vec::new(), 1);
+        assert_eq!(carver.find_seam(4, 3), vec!(0, 5, 8));
+    }
+
+    #[test]
+    fn keeps_distances_unless_the_guide_changes() {
+        let image = noisy_image(4, 3);
+        let mut carver = Carver::new(4 * 3);
+        carver.calculate_energy(4, 3, &image);
+        carver.set_guide(vec!(2, 2, 2), 1);
+        carver.find_seam(4, 3);
+
+        carver.set_guide(vec!(2, 2, 2), 1);
+        assert!(carver.distances().is_some());
+        carver.set_guide(vec!(2, 2, 2), 2);
+        assert!(carver.distances().is_none());
+        carver.find_seam(4, 3);
+        carver.set_guide(vec!(1, 2, 2), 2);
+        assert!(carver.distances().is_none());
+    }
+
+    #[test]
+    fn removes_seams_from_mask_too() {
+        let mut mask = vec![Mark::Unmarked; 4 * 3];
+        mask[6] = Mark::Remove;
+        mask[7] = Mark::Remove;
+        let image = vec![rgb(50, 50, 50); 4 * 3];
+        let mut carver = Carver::new(4 * 3);
+        carver.set_mask(mask);
+        carver.calculate_energy(4, 3, &image);
+
+        carver.remove_seam(4, 3, Orientation::Vertical, &vec!(1, 6, 9));
+
+        // pixel 7 (the last in the middle row) has moved along to become pixel 5 of the 3 x 3 image
+        assert_eq!(carver.marked_for_removal(3, 3), vec!(5));
+    }
+
+    #[test]
+    fn updates_energy_and_finds_seams_as_if_recalculated_from_scratch() {
+        let options = [
+            CarverOptions::default(),
+            CarverOptions { seam_cost: SeamCost::Forward, ..CarverOptions::default() },
+            CarverOptions { energy: Energy::Entropy, border: BorderPolicy::Mirrored, ..CarverOptions::default() },
+            CarverOptions { energy: Energy::Sobel, border: BorderPolicy::Wrapped, ..CarverOptions::default() },
+            CarverOptions { energy: Energy::Saliency, ..CarverOptions::default() },
+        ];
+        let orientations = [Orientation::Vertical, Orientation::Vertical, Orientation::Horizontal,
+            Orientation::Vertical, Orientation::Horizontal, Orientation::Horizontal, Orientation::Vertical];
+
+        for &options in options.iter() {
+            let (mut width, mut height) = (14, 11);
+            let mut pixels = noisy_image(width, height);
+            let mut incremental = Carver::with_options(width * height, options);
+            incremental.calculate_energy(width, height, &pixels);
+
+            for &orientation in orientations.iter() {
+                let mut fresh = Carver::with_options(width * height, options);
+                fresh.calculate_energy(width, height, &pixels);
+                let seam = incremental.find_oriented_seam(width, height, orientation);
+
+                assert_eq!(incremental.energy, fresh.energy);
+                assert_eq!(seam, fresh.find_oriented_seam(width, height, orientation));
+                assert_eq!(incremental.last_seam_cost(), fresh.last_seam_cost());
+
+                let (new_width, new_height) = lazy_remove_seam(&mut pixels[..], width, height, orientation, &seam);
+                pixels.truncate(new_width * new_height);
+                incremental.remove_seam(width, height, orientation, &seam);
+                width = new_width;
+                height = new_height;
+            }
+        }
+    }
+
+    #[test]
+    fn finds_same_seams_whether_split_between_threads_or_not() {
+        // wide enough for 3 bands; the flat image has ties everywhere, which must be broken the same way
+        let (width, height) = (3 * MIN_BAND_BREADTH + 5, 4);
+        let images = [noisy_image(width, height), vec![rgb(50, 50, 50); width * height]];
+
+        for image in images.iter() {
+            for &seam_cost in [SeamCost::Backward, SeamCost::Forward].iter() {
+                let find_with_threads = |threads: usize| {
+                    let options = CarverOptions { seam_cost: seam_cost, threads: threads, ..CarverOptions::default() };
+                    let mut carver = Carver::with_options(width * height, options);
+                    carver.calculate_energy(width, height, image);
+                    let seam = carver.find_seam(width, height);
+                    (seam, carver.last_seam_cost())
+                };
+
+                assert_eq!(find_with_threads(3), find_with_threads(1));
+            }
+        }
+    }
+
+    #[test]
+    fn reuses_bands_for_images_split_into_fewer_of_them() {
+        let (width, height) = (3 * MIN_BAND_BREADTH + 5, 4);
+        let options = CarverOptions { threads: 3, ..CarverOptions::default() };
+        let mut reused = Carver::with_options(width * height, options);
+        let wide = noisy_image(width, height);
+        reused.calculate_energy(width, height, &wide);
+        reused.find_seam(width, height);
+
+        // 2 bands of different breadths, then 1, each of which must only use the start of the bands' vectors
+        for &(width, height) in [(2 * MIN_BAND_BREADTH + 1, 5), (7, 3)].iter() {
+            let image = noisy_image(width, height);
+            let mut fresh = Carver::with_options(width * height, options);
+            fresh.calculate_energy(width, height, &image);
+            reused.calculate_energy(width, height, &image);
+
+            assert_eq!(reused.find_seam(width, height), fresh.find_seam(width, height));
+            assert_eq!(reused.last_seam_cost(), fresh.last_seam_cost());
+        }
+    }
+
+    #[cfg(feature = "bench")]
+    #[bench]
+    fn bench_removing_seams_recalculating_all_energy(b: &mut Bencher) {
+        let image = noisy_image(300, 200);
+        let mut carver = Carver::new(300 * 200);
+        b.iter(|| remove_seams(&mut carver, &mut image.clone(), 300, 200, 20, false));
+    }
+
+    #[cfg(feature = "bench")]
+    #[bench]
+    fn bench_removing_seams_updating_energy_incrementally(b: &mut Bencher) {
+        let image = noisy_image(300, 200);
+        let mut carver = Carver::new(300 * 200);
+        b.iter(|| remove_seams(&mut carver, &mut image.clone(), 300, 200, 20, true));
+    }
 }
@@ -0,0 +1,331 @@
+use lodepng::RGB;
+use std::cmp;
+
+// as indicated by the spec, this is the energy of a complete standout pixel, and is also used for pixels on the edge.
+pub const MAX_PIXEL_ENERGY: i32 = 255 * 255 * 3;
+
+/// Calculates how important each pixel of an image is, so that seams avoid high energy pixels.
+pub trait EnergyFunction {
+    /// Called once before the energy of each pixel in an image is calculated, for functions which need to look at the
+    /// whole image (the default does nothing).
+    #[allow(unused_variables)]
+    fn prepare(&mut self, pixels: &[RGB<u8>], width: usize, height: usize) {}
+
+    /// The energy of the pixel at (`x`, `y`). Other pixels should be read through `pixel_at`, which also accepts
+    /// coordinates outside the image (resolving them according to the border policy).
+    fn energy(&self, pixel_at: &Fn(isize, isize) -> RGB<u8>, x: isize, y: isize) -> i32;
+
+    /// How far away from a pixel `energy` looks, or `None` if it can depend on the whole image (in which case energy
+    /// has to be recalculated from scratch after every seam removal).
+    fn radius(&self) -> Option<usize>;
+
+    /// The energy given to border pixels under `BorderPolicy::MaxEnergy`.
+    fn max_energy(&self) -> i32 {
+        MAX_PIXEL_ENERGY
+    }
+}
+
+/// The available energy functions, for choosing one by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Energy {
+    DualGradient,
+    Sobel,
+    Scharr,
+    Entropy,
+    Saliency,
+}
+
+impl Energy {
+    pub fn from_name(name: &str) -> Option<Energy> {
+        match name {
+            "dual-gradient" => Some(Energy::DualGradient),
+            "sobel" => Some(Energy::Sobel),
+            "scharr" => Some(Energy::Scharr),
+            "entropy" => Some(Energy::Entropy),
+            "saliency" => Some(Energy::Saliency),
+            _ => None,
+        }
+    }
+
+    pub fn function(&self) -> Box<EnergyFunction> {
+        match *self {
+            Energy::DualGradient => Box::new(DualGradient),
+            Energy::Sobel => Box::new(Kernel::sobel()),
+            Energy::Scharr => Box::new(Kernel::scharr()),
+            Energy::Entropy => Box::new(Entropy),
+            Energy::Saliency => Box::new(Saliency::new()),
+        }
+    }
+}
+
+/// What to do about pixels whose neighbours are outside the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BorderPolicy {
+    /// Border pixels get the maximum energy (as in the spec), which keeps seams away from the edges. Functions which
+    /// look further than 1 pixel away see the edge pixels repeated.
+    MaxEnergy,
+    /// Pixels outside the image are reflected back in, so a border pixel's missing neighbour is its opposite neighbour.
+    Mirrored,
+    /// The image wraps around, so the left edge neighbours the right and the top neighbours the bottom.
+    Wrapped,
+}
+
+impl BorderPolicy {
+    pub fn from_name(name: &str) -> Option<BorderPolicy> {
+        match name {
+            "max" => Some(BorderPolicy::MaxEnergy),
+            "mirrored" => Some(BorderPolicy::Mirrored),
+            "wrapped" => Some(BorderPolicy::Wrapped),
+            _ => None,
+        }
+    }
+
+    /// Maps a (possibly out of range) coordinate along an axis of the given size to one inside the image.
+    pub fn resolve(&self, coord: isize, size: usize) -> usize {
+        let size = size as isize;
+        let resolved = match *self {
+            BorderPolicy::MaxEnergy => coord,
+            BorderPolicy::Mirrored => if coord < 0 {
+                -coord
+            } else if coord >= size {
+                2 * (size - 1) - coord
+            } else {
+                coord
+            },
+            BorderPolicy::Wrapped => ((coord % size) + size) % size,
+        };
+        cmp::max(0, cmp::min(resolved, size - 1)) as usize // clamp anything still outside (e.g. on tiny images)
+    }
+}
+
+pub fn colour_distance(a: RGB<u8>, b: RGB<u8>) -> i32 {
+    (a.r as i32 - b.r as i32).pow(2) + (a.g as i32 - b.g as i32).pow(2) + (a.b as i32 - b.b as i32).pow(2)
+}
+
+/// The energy function from the spec: squared colour difference between the pixels either side horizontally plus that
+/// of the pixels either side vertically.
+pub struct DualGradient;
+
+impl EnergyFunction for DualGradient {
+    fn energy(&self, pixel_at: &Fn(isize, isize) -> RGB<u8>, x: isize, y: isize) -> i32 {
+        let energy_x = colour_distance(pixel_at(x - 1, y), pixel_at(x + 1, y));
+        let energy_y = colour_distance(pixel_at(x, y - 1), pixel_at(x, y + 1));
+        energy_x + energy_y
+    }
+
+    fn radius(&self) -> Option<usize> {
+        Some(1)
+    }
+}
+
+/// A 3 x 3 gradient kernel (like Sobel or Scharr) which smooths across the direction of the gradient. Results are
+/// normalised by the kernel weights so they're in the same units as `DualGradient`.
+pub struct Kernel {
+    side_weight: i32,
+    centre_weight: i32,
+}
+
+impl Kernel {
+    pub fn sobel() -> Kernel {
+        Kernel { side_weight: 1, centre_weight: 2 }
+    }
+
+    pub fn scharr() -> Kernel {
+        Kernel { side_weight: 3, centre_weight: 10 }
+    }
+}
+
+impl EnergyFunction for Kernel {
+    fn energy(&self, pixel_at: &Fn(isize, isize) -> RGB<u8>, x: isize, y: isize) -> i32 {
+        let weights = [self.side_weight, self.centre_weight, self.side_weight];
+        let norm = weights.iter().fold(0, |sum, w| sum + w);
+
+        let mut energy = 0;
+        for channel in 0..3 {
+            let value = |dx: isize, dy: isize| {
+                let p = pixel_at(x + dx, y + dy);
+                (match channel { 0 => p.r, 1 => p.g, _ => p.b }) as i32
+            };
+            let (mut gx, mut gy) = (0, 0);
+            for (i, &w) in weights.iter().enumerate() {
+                let d = i as isize - 1;
+                gx += w * (value(1, d) - value(-1, d));
+                gy += w * (value(d, 1) - value(d, -1));
+            }
+            energy += (gx * gx + gy * gy) / (norm * norm);
+        }
+        energy
+    }
+
+    fn radius(&self) -> Option<usize> {
+        Some(1)
+    }
+}
+
+/// Shannon entropy of the grey levels in the 9 x 9 window around each pixel, scaled so a window spread evenly over
+/// every grey level has `MAX_PIXEL_ENERGY`. Textured areas get high energy even where gradients are small.
+pub struct Entropy;
+
+const ENTROPY_RADIUS: isize = 4;
+const ENTROPY_BINS: usize = 16;
+
+impl EnergyFunction for Entropy {
+    fn energy(&self, pixel_at: &Fn(isize, isize) -> RGB<u8>, x: isize, y: isize) -> i32 {
+        let mut histogram = [0u32; ENTROPY_BINS];
+        for dy in -ENTROPY_RADIUS..(ENTROPY_RADIUS + 1) {
+            for dx in -ENTROPY_RADIUS..(ENTROPY_RADIUS + 1) {
+                let p = pixel_at(x + dx, y + dy);
+                let grey = (p.r as usize + p.g as usize + p.b as usize) / 3;
+                histogram[grey * ENTROPY_BINS / 256] += 1;
+            }
+        }
+
+        let samples = ((ENTROPY_RADIUS * 2 + 1) * (ENTROPY_RADIUS * 2 + 1)) as f64;
+        let entropy = histogram.iter().filter(|&&count| count > 0).fold(0.0, |sum, &count| {
+            let p = count as f64 / samples;
+            sum - p * p.log2()
+        });
+        let max_entropy = (ENTROPY_BINS as f64).log2();
+        (entropy / max_entropy * MAX_PIXEL_ENERGY as f64) as i32
+    }
+
+    fn radius(&self) -> Option<usize> {
+        Some(ENTROPY_RADIUS as usize)
+    }
+}
+
+/// `DualGradient` weighted by how much each pixel stands out from the image as a whole (the distance of its slightly
+/// blurred colour from the mean colour of the image, as in "Frequency-tuned Salient Region Detection", Achanta et al.,
+/// 2009). The least salient pixels get half their gradient energy and the most salient get all of it.
+pub struct Saliency {
+    saliency: Vec<f64>, // per pixel, from 0 to 1
+    width: usize,
+}
+
+impl Saliency {
+    pub fn new() -> Saliency {
+        Saliency { saliency: Vec::new(), width: 0 }
+    }
+}
+
+impl EnergyFunction for Saliency {
+    fn prepare(&mut self, pixels: &[RGB<u8>], width: usize, height: usize) {
+        let num_pixels = width * height;
+        let (mut r, mut g, mut b) = (0u64, 0u64, 0u64);
+        for p in pixels[..num_pixels].iter() {
+            r += p.r as u64;
+            g += p.g as u64;
+            b += p.b as u64;
+        }
+        let n = cmp::max(num_pixels, 1) as u64;
+        let mean = RGB { r: (r / n) as u8, g: (g / n) as u8, b: (b / n) as u8 };
+
+        self.width = width;
+        self.saliency.clear();
+        let mut max_saliency = 0.0f64;
+        for y in 0..height {
+            for x in 0..width {
+                // 3 x 3 box blur, repeating edge pixels
+                let (mut r, mut g, mut b) = (0u32, 0u32, 0u32);
+                for dy in 0..3 {
+                    for dx in 0..3 {
+                        let sx = BorderPolicy::MaxEnergy.resolve(x as isize + dx - 1, width);
+                        let sy = BorderPolicy::MaxEnergy.resolve(y as isize + dy - 1, height);
+                        let p = pixels[sy * width + sx];
+                        r += p.r as u32;
+                        g += p.g as u32;
+                        b += p.b as u32;
+                    }
+                }
+                let blurred = RGB { r: (r / 9) as u8, g: (g / 9) as u8, b: (b / 9) as u8 };
+                let saliency = colour_distance(blurred, mean) as f64;
+                max_saliency = max_saliency.max(saliency);
+                self.saliency.push(saliency);
+            }
+        }
+
+        if max_saliency > 0.0 {
+            for s in self.saliency.iter_mut() {
+                *s /= max_saliency;
+            }
+        }
+    }
+
+    fn energy(&self, pixel_at: &Fn(isize, isize) -> RGB<u8>, x: isize, y: isize) -> i32 {
+        let saliency = self.saliency[y as usize * self.width + x as usize];
+        (DualGradient.energy(pixel_at, x, y) as f64 * (1.0 + saliency) / 2.0) as i32
+    }
+
+    fn radius(&self) -> Option<usize> {
+        None // the mean colour of the image changes with every seam removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lodepng::RGB;
+    use super::*;
+
+    fn grey(v: u8) -> RGB<u8> {
+        RGB { r: v, g: v, b: v }
+    }
+
+    /// Runs the function on the centre pixel of a 3 x 3 image given as grey levels, repeating edge pixels beyond it.
+    fn centre_energy(function: &mut EnergyFunction, greys: [u8; 9]) -> i32 {
+        let pixels = greys.iter().map(|&v| grey(v)).collect::<Vec<_>>();
+        function.prepare(&pixels, 3, 3);
+        let pixel_at = |x: isize, y: isize|
+            pixels[BorderPolicy::MaxEnergy.resolve(y, 3) * 3 + BorderPolicy::MaxEnergy.resolve(x, 3)];
+        function.energy(&pixel_at, 1, 1)
+    }
+
+    #[test]
+    fn resolves_coordinates_outside_image_according_to_policy() {
+        assert_eq!(BorderPolicy::Mirrored.resolve(-1, 5), 1);
+        assert_eq!(BorderPolicy::Mirrored.resolve(5, 5), 3);
+        assert_eq!(BorderPolicy::Wrapped.resolve(-1, 5), 4);
+        assert_eq!(BorderPolicy::Wrapped.resolve(5, 5), 0);
+        assert_eq!(BorderPolicy::MaxEnergy.resolve(-3, 5), 0);
+        assert_eq!(BorderPolicy::Mirrored.resolve(2, 5), 2);
+    }
+
+    #[test]
+    fn gradient_kernels_match_dual_gradient_on_a_linear_ramp() {
+        // every column is 10 brighter than the last, so every gradient function sees the same horizontal difference
+        let ramp = [0, 10, 20, 0, 10, 20, 0, 10, 20];
+
+        let dual_gradient = centre_energy(&mut DualGradient, ramp);
+
+        assert_eq!(dual_gradient, 3 * 20 * 20);
+        assert_eq!(centre_energy(&mut Kernel::sobel(), ramp), dual_gradient);
+        assert_eq!(centre_energy(&mut Kernel::scharr(), ramp), dual_gradient);
+    }
+
+    #[test]
+    fn sobel_smooths_across_the_gradient() {
+        // only the bottom row changes, which dual gradient ignores at the centre but sobel weights by a quarter
+        let step = [0, 0, 0, 0, 0, 0, 0, 0, 200];
+
+        assert_eq!(centre_energy(&mut DualGradient, step), 0);
+        assert_eq!(centre_energy(&mut Kernel::sobel(), step), 2 * 3 * 50 * 50);
+    }
+
+    #[test]
+    fn entropy_is_zero_for_flat_areas_and_high_for_texture() {
+        let flat = centre_energy(&mut Entropy, [100; 9]);
+        let textured = centre_energy(&mut Entropy, [0, 32, 64, 96, 128, 160, 192, 224, 255]);
+
+        assert_eq!(flat, 0);
+        assert!(textured > MAX_PIXEL_ENERGY / 2, "textured energy was {}", textured);
+    }
+
+    #[test]
+    fn saliency_halves_energy_of_least_salient_pixels() {
+        // the centre is the most average pixel, so has no saliency
+        let greys = [0, 100, 200, 0, 100, 200, 0, 100, 200];
+
+        let dual_gradient = centre_energy(&mut DualGradient, greys);
+
+        assert_eq!(centre_energy(&mut Saliency::new(), greys), dual_gradient / 2);
+    }
+}
@@ -0,0 +1,36 @@
+use std::error;
+use std::fmt;
+
+/// Everything that can go wrong carving, loading or saving images (apart from mistakes in how the library is called,
+/// such as passing pixels which don't match the given width and height, which still panic).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// A `width` x `height` image can't be resized to `new_width` x `new_height`, e.g. because that would leave it
+    /// empty, or because only shrinking is supported.
+    InvalidSize { width: usize, height: usize, new_width: usize, new_height: usize },
+    /// A file couldn't be read or written.
+    Io(String),
+    /// A file's contents aren't an image (or seam history) we can read, or an image can't be encoded.
+    Format(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidSize { width, height, new_width, new_height } =>
+                write!(f, "can't resize a {} x {} image to {} x {}", width, height, new_width, new_height),
+            Error::Io(ref reason) => write!(f, "{}", reason),
+            Error::Format(ref reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::InvalidSize { .. } => "invalid image size",
+            Error::Io(ref reason) => reason,
+            Error::Format(ref reason) => reason,
+        }
+    }
+}
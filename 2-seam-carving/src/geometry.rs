@@ -0,0 +1,98 @@
+use std::cmp;
+
+/// A size to resize an image to, given relative to the image's own size: a new width and/or height, a scale, and/or
+/// an aspect ratio. Anything not given is left as it is, so unlike ImageMagick's `-resize`, a new width alone doesn't
+/// change the height.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Geometry {
+    pub width: Option<usize>,
+    pub height: Option<usize>,
+    pub scale: Option<f64>, // of both the width and height, e.g. 0.75 for 75%
+    pub aspect: Option<(f64, f64)>, // width to height, e.g. (16.0, 9.0)
+}
+
+impl Geometry {
+    /// The size a `width` x `height` image should be resized to. The scale applies first, then any new width or
+    /// height. An aspect ratio then sets the height from the width if only a width was given (or the width from the
+    /// height), and otherwise shrinks whichever dimension is too big for it, so seams are only removed to reach it.
+    /// Both dimensions are rounded to the nearest pixel, but left at least 1.
+    pub fn size_for(&self, width: usize, height: usize) -> (usize, usize) {
+        let (mut new_width, mut new_height) = match self.scale {
+            Some(scale) => (width as f64 * scale, height as f64 * scale),
+            None => (width as f64, height as f64),
+        };
+        if let Some(width) = self.width {
+            new_width = width as f64;
+        }
+        if let Some(height) = self.height {
+            new_height = height as f64;
+        }
+        if let Some((aspect_width, aspect_height)) = self.aspect {
+            let ratio = aspect_width / aspect_height;
+            match (self.width, self.height) {
+                (Some(_), None) => new_height = new_width / ratio,
+                (None, Some(_)) => new_width = new_height * ratio,
+                _ if new_width > new_height * ratio => new_width = new_height * ratio,
+                _ => new_height = new_width / ratio,
+            }
+        }
+        let round = |length: f64| cmp::max(1, length.round() as usize);
+        (round(new_width), round(new_height))
+    }
+}
+
+/// Parses a percentage such as `75%` (or just `75`) into a scale such as 0.75. It must be more than 0.
+pub fn parse_scale(s: &str) -> Option<f64> {
+    let number = if s.ends_with('%') { &s[..s.len() - 1] } else { s };
+    match number.parse::<f64>() {
+        Ok(percent) if percent > 0.0 && percent.is_finite() => Some(percent / 100.0),
+        _ => None,
+    }
+}
+
+/// Parses an aspect ratio such as `16:9` (or `1.85:1`). Neither side can be 0.
+pub fn parse_aspect(s: &str) -> Option<(f64, f64)> {
+    let mut parts = s.splitn(2, ':');
+    match (parts.next().and_then(|w| w.parse::<f64>().ok()), parts.next().and_then(|h| h.parse::<f64>().ok())) {
+        (Some(w), Some(h)) if w > 0.0 && h > 0.0 && w.is_finite() && h.is_finite() => Some((w, h)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_aspect, parse_scale, Geometry};
+
+    #[test]
+    fn parses_scales_and_aspect_ratios() {
+        assert_eq!(parse_scale("75%"), Some(0.75));
+        assert_eq!(parse_scale("150"), Some(1.5));
+        assert_eq!(parse_scale("0%"), None);
+        assert_eq!(parse_scale("big"), None);
+        assert_eq!(parse_aspect("16:9"), Some((16.0, 9.0)));
+        assert_eq!(parse_aspect("1.85:1"), Some((1.85, 1.0)));
+        assert_eq!(parse_aspect("16:0"), None);
+        assert_eq!(parse_aspect("16x9"), None);
+    }
+
+    #[test]
+    fn sizes_images_relative_to_their_own_size() {
+        let width = |width| Geometry { width: Some(width), ..Geometry::default() };
+        let scale = |scale| Geometry { scale: Some(scale), ..Geometry::default() };
+        let aspect = |w, h| Geometry { aspect: Some((w, h)), ..Geometry::default() };
+
+        assert_eq!(Geometry::default().size_for(1000, 600), (1000, 600));
+        assert_eq!(width(800).size_for(1000, 600), (800, 600));
+        assert_eq!(Geometry { height: Some(900), ..Geometry::default() }.size_for(1000, 600), (1000, 900));
+        assert_eq!(width(1200).size_for(1000, 600), (1200, 600));
+        assert_eq!(scale(0.75).size_for(1000, 600), (750, 450));
+        assert_eq!(scale(0.01).size_for(10, 30), (1, 1));
+        // too tall for 16:9, so the height shrinks; too wide, so the width does
+        assert_eq!(aspect(16.0, 9.0).size_for(1000, 600), (1000, 563));
+        assert_eq!(aspect(1.0, 1.0).size_for(1000, 600), (600, 600));
+        assert_eq!(Geometry { aspect: Some((16.0, 9.0)), ..width(800) }.size_for(1000, 600), (800, 450));
+        assert_eq!(Geometry { aspect: Some((4.0, 3.0)), height: Some(300), ..Geometry::default() }.size_for(1000, 600),
+            (400, 300));
+        assert_eq!(Geometry { aspect: Some((1.0, 1.0)), ..scale(0.5) }.size_for(1000, 600), (300, 300));
+    }
+}
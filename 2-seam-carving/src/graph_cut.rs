@@ -0,0 +1,129 @@
+use carving::{Carver, Orientation, REMOVAL_ENERGY};
+use graph::flow_network::{FlowEdge, FlowNetwork};
+use graph::ford_fulkerson;
+use std::f64;
+
+/// How far apart the pixels of a seam found by a graph cut can be from one step to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connectivity {
+    Connected, // at most 1 pixel across, like the seams found by `Carver::find_oriented_seam`
+    Piecewise, // any distance, so the seam can jump between cheap areas (but still has 1 pixel per step)
+}
+
+impl Connectivity {
+    pub fn from_name(name: &str) -> Option<Connectivity> {
+        match name {
+            "connected" => Some(Connectivity::Connected),
+            "piecewise" => Some(Connectivity::Piecewise),
+            _ => None,
+        }
+    }
+}
+
+/// Finds the seam with the lowest total energy (as calculated by `carver`, including any mask) as the minimum cut of a
+/// graph of the image's pixels, rather than by dynamic programming, so seams needn't be connected. This is much slower
+/// than `Carver::find_oriented_seam` (and ignores the carver's seam cost), so it's mainly for comparison.
+///
+/// Each pixel is a vertex, with an edge to the next pixel across the seam whose capacity is the pixel's energy, so
+/// cutting it removes the pixel; the source has an edge to the first pixel of each step, and the last pixel of each
+/// step has an edge to the sink. Infinite edges back across the seam stop a step being cut more than once, and for
+/// connected seams, infinite edges back and diagonally into the neighbouring steps stop the cut moving more than 1
+/// pixel between steps. The last pixel on the source side of each step is removed.
+pub fn find_seam(carver: &Carver, width: usize, height: usize, orientation: Orientation, connectivity: Connectivity)
+        -> Vec<usize> {
+    let (length, breadth) = match orientation {
+        Orientation::Vertical => (height, width),
+        Orientation::Horizontal => (width, height),
+    };
+    let pixel_at = |step: usize, offset: usize| match orientation {
+        Orientation::Vertical => step * width + offset,
+        Orientation::Horizontal => offset * width + step,
+    };
+    let source = width * height;
+    let sink = source + 1;
+
+    let mut network = FlowNetwork::new(width * height + 2);
+    for step in 0..length {
+        network.add_edge(FlowEdge::new(source, pixel_at(step, 0), f64::INFINITY, 0.0));
+        for offset in 0..breadth {
+            let pixel = pixel_at(step, offset);
+            // energy can be negative for pixels marked for removal, but capacities can't; the same amount is cut in
+            // every step, so raising every capacity by the same amount doesn't change which cut is cheapest
+            let capacity = carver.energy[pixel] as f64 - REMOVAL_ENERGY as f64;
+            let next = if offset + 1 < breadth { pixel_at(step, offset + 1) } else { sink };
+            network.add_edge(FlowEdge::new(pixel, next, capacity, 0.0));
+            if offset > 0 {
+                network.add_edge(FlowEdge::new(pixel, pixel_at(step, offset - 1), f64::INFINITY, 0.0));
+                if connectivity == Connectivity::Connected {
+                    if step > 0 {
+                        network.add_edge(FlowEdge::new(pixel, pixel_at(step - 1, offset - 1), f64::INFINITY, 0.0));
+                    }
+                    if step + 1 < length {
+                        network.add_edge(FlowEdge::new(pixel, pixel_at(step + 1, offset - 1), f64::INFINITY, 0.0));
+                    }
+                }
+            }
+        }
+    }
+
+    let cut = ford_fulkerson::calculate(&network, source, sink);
+    (0..length).map(|step| {
+        let removed = (0..breadth).take_while(|&offset| cut.in_cut(pixel_at(step, offset))).count() - 1;
+        pixel_at(step, removed)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use carving::{Carver, CarverOptions, Orientation};
+    use energy::BorderPolicy;
+    use lodepng::Grey;
+    use noisy_image;
+    use super::{find_seam, Connectivity};
+
+    /// A carver for a noisy image, with a mirrored border so the first and last rows aren't all equally expensive.
+    fn noisy_carver(width: usize, height: usize) -> Carver {
+        let pixels = noisy_image(width, height).iter().map(|noise| Grey(noise.r)).collect::<Vec<_>>();
+        let options = CarverOptions { border: BorderPolicy::Mirrored, ..CarverOptions::default() };
+        let mut carver = Carver::with_options(width * height, options);
+        carver.calculate_energy(width, height, &pixels);
+        carver
+    }
+
+    fn energy_of(carver: &Carver, seam: &[usize]) -> i64 {
+        seam.iter().map(|&pixel| carver.energy[pixel] as i64).sum()
+    }
+
+    #[test]
+    fn finds_connected_seams_as_cheap_as_dynamic_programming() {
+        for &orientation in [Orientation::Vertical, Orientation::Horizontal].iter() {
+            let mut carver = noisy_carver(7, 6);
+            let dynamic = carver.find_oriented_seam(7, 6, orientation);
+
+            let cut = find_seam(&carver, 7, 6, orientation, Connectivity::Connected);
+
+            assert_eq!(cut.len(), dynamic.len());
+            assert_eq!(energy_of(&carver, &cut), energy_of(&carver, &dynamic));
+            for pair in cut.windows(2) {
+                let (a, b) = match orientation {
+                    Orientation::Vertical => (pair[0] % 7, pair[1] % 7),
+                    Orientation::Horizontal => (pair[0] / 7, pair[1] / 7),
+                };
+                assert!(a + 1 >= b && b + 1 >= a, "seam {:?} isn't connected", cut);
+            }
+        }
+    }
+
+    #[test]
+    fn finds_piecewise_seams_through_the_cheapest_pixel_of_each_step() {
+        let carver = noisy_carver(7, 6);
+
+        let connected = find_seam(&carver, 7, 6, Orientation::Vertical, Connectivity::Connected);
+        let piecewise = find_seam(&carver, 7, 6, Orientation::Vertical, Connectivity::Piecewise);
+
+        let cheapest = carver.energy.chunks(7).map(|row| *row.iter().min().unwrap() as i64).sum();
+        assert_eq!(energy_of(&carver, &piecewise), cheapest);
+        assert!(energy_of(&carver, &piecewise) < energy_of(&carver, &connected));
+        assert!(piecewise.iter().enumerate().all(|(row, &pixel)| pixel / 7 == row));
+    }
+}
@@ -0,0 +1,357 @@
+use carving::{Carver, Orientation};
+use pixel::Pixel;
+use {remove_seam, Error, Image};
+
+const MAGIC: &'static [u8] = b"SEAMHIST";
+
+/// A seam which was removed from an image, along with the pixels it removed so it can be put back.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemovedSeam<P> {
+    pub orientation: Orientation,
+    pub seam: Vec<usize>, // indexes in the image the seam was removed from
+    pub pixels: Vec<P>, // the pixel at each index
+}
+
+/// Every seam removed from an image, in the order they were removed, along with the image they were removed to leave.
+/// This is enough to rebuild the image as it was after any number of the removals (such as for a width slider) without
+/// searching for the seams again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeamHistory<P> {
+    pub seams: Vec<RemovedSeam<P>>,
+    pub result: Image<P>,
+}
+
+impl<P: Pixel> SeamHistory<P> {
+    /// A history of the removals from an image, which starts off as the image itself.
+    pub fn new(image: &Image<P>) -> SeamHistory<P> {
+        SeamHistory { seams: Vec::new(), result: image.clone() }
+    }
+
+    /// Removes `seam` (as found by `carver`) from the resulting image like `remove_seam`, recording it and the pixels
+    /// it removes.
+    pub fn remove_seam(&mut self, carver: &mut Carver, orientation: Orientation, seam: &Vec<usize>) {
+        self.seams.push(removed_from(&self.result, orientation, seam));
+        remove_seam(carver, &mut self.result, orientation, seam);
+    }
+
+    /// Records `seam` and its pixels as they are about to be removed from `image`, for when the image is carved
+    /// separately. `result` should be set to the image once it's finished.
+    pub fn record(&mut self, image: &Image<P>, orientation: Orientation, seam: &Vec<usize>) {
+        self.seams.push(removed_from(image, orientation, seam));
+    }
+
+    /// The image as it was after the first `removed` seams were removed, so 0 gives the original image back exactly.
+    pub fn image_after(&self, removed: usize) -> Image<P> {
+        assert!(removed <= self.seams.len(), "can't go past the last removal");
+        self.seams[removed..].iter().rev().fold(self.result.clone(), |image, seam| restore_seam(&image, seam))
+    }
+
+    /// The number of removals which leave the image `width` pixels wide, if any do (taking the first, so any
+    /// horizontal seams removed at that width are still in the image).
+    pub fn removals_to_width(&self, width: usize) -> Option<usize> {
+        let mut current = self.result.width + self.seams.iter()
+            .filter(|removed| removed.orientation == Orientation::Vertical).count();
+        for (i, removed) in self.seams.iter().enumerate() {
+            if current == width {
+                return Some(i);
+            }
+            if removed.orientation == Orientation::Vertical {
+                current -= 1;
+            }
+        }
+        if current == width { Some(self.seams.len()) } else { None }
+    }
+
+    /// The number of removals which leave the image `width` x `height`, if any do. Every removal changes the size, so
+    /// at most one does; unlike a width alone, this can reach any image in between when horizontal seams were removed.
+    pub fn removals_to_size(&self, width: usize, height: usize) -> Option<usize> {
+        let (mut current_width, mut current_height) = (self.result.width, self.result.height);
+        for removed in &self.seams {
+            match removed.orientation {
+                Orientation::Vertical => current_width += 1,
+                Orientation::Horizontal => current_height += 1,
+            }
+        }
+        for (i, removed) in self.seams.iter().enumerate() {
+            if (current_width, current_height) == (width, height) {
+                return Some(i);
+            }
+            match removed.orientation {
+                Orientation::Vertical => current_width -= 1,
+                Orientation::Horizontal => current_height -= 1,
+            }
+        }
+        if (current_width, current_height) == (width, height) { Some(self.seams.len()) } else { None }
+    }
+
+    /// The history as a side file: a header naming the pixel format, then the resulting image and each seam with its
+    /// pixels, all as little endian integers with each channel in 16 bits.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        let name = P::format_name().as_bytes();
+        data.push(name.len() as u8);
+        data.extend_from_slice(name);
+        push_u32(&mut data, self.result.width);
+        push_u32(&mut data, self.result.height);
+        push_pixels(&mut data, &self.result.pixels);
+        push_u32(&mut data, self.seams.len());
+        for removed in &self.seams {
+            data.push(match removed.orientation {
+                Orientation::Vertical => 0,
+                Orientation::Horizontal => 1,
+            });
+            push_u32(&mut data, removed.seam.len());
+            for &i in &removed.seam {
+                push_u32(&mut data, i);
+            }
+            push_pixels(&mut data, &removed.pixels);
+        }
+        data
+    }
+
+    /// Reads a history written by `to_bytes`, which must have been written with the same pixel format. Every seam is
+    /// checked against the size of the image it was removed from, so the image can be rebuilt after any removal.
+    pub fn from_bytes(data: &[u8]) -> Result<SeamHistory<P>, Error> {
+        let name = try!(format_name_of(data).ok_or(Error::Format("not a seam history".to_string())));
+        if name != P::format_name() {
+            return Err(Error::Format(format!("seam history has {} pixels, not {}", name, P::format_name())));
+        }
+        let mut reader = Reader { data: data, position: MAGIC.len() + 1 + name.len() };
+
+        let width = try!(reader.u32());
+        let height = try!(reader.u32());
+        let pixels = try!(reader.pixels(try!(width.checked_mul(height).ok_or(cut_short()))));
+        let mut seams = Vec::new();
+        for _ in 0..try!(reader.u32()) {
+            let orientation = match try!(reader.bytes(1))[0] {
+                0 => Orientation::Vertical,
+                1 => Orientation::Horizontal,
+                other => return Err(Error::Format(format!("unknown seam orientation {}", other))),
+            };
+            let length = try!(reader.u32());
+            let seam = try!(reader.u32s(length));
+            let pixels = try!(reader.pixels(length));
+            seams.push(RemovedSeam { orientation: orientation, seam: seam, pixels: pixels });
+        }
+        try!(check_seams(&seams, width, height));
+        Ok(SeamHistory { seams: seams, result: Image::new(pixels, width, height) })
+    }
+}
+
+/// Checks that each seam has exactly 1 pixel in each row (or column, for horizontal seams) of the image it was removed
+/// from, working back from the `width` x `height` result, so `restore_seam` can put every one of them back.
+fn check_seams<P>(seams: &[RemovedSeam<P>], width: usize, height: usize) -> Result<(), Error> {
+    let (mut width, mut height) = (width, height);
+    for (n, removed) in seams.iter().enumerate().rev() {
+        let fits = match removed.orientation {
+            Orientation::Vertical => {
+                width = try!(width.checked_add(1).ok_or(cut_short()));
+                removed.seam.len() == height && removed.seam.iter().enumerate().all(|(y, &i)| i / width == y)
+            },
+            Orientation::Horizontal => {
+                height = try!(height.checked_add(1).ok_or(cut_short()));
+                removed.seam.len() == width &&
+                    removed.seam.iter().enumerate().all(|(x, &i)| i % width == x && i / width < height)
+            },
+        };
+        if !fits {
+            return Err(Error::Format(format!("seam {} of the history doesn't fit the {} x {} image it was removed from",
+                n + 1, width, height)));
+        }
+    }
+    Ok(())
+}
+
+fn cut_short() -> Error {
+    Error::Format("seam history is cut short".to_string())
+}
+
+/// The pixel format (see `Pixel::format_name`) of a history written by `SeamHistory::to_bytes`, so it can be read with
+/// the right type of pixel. `None` if the data isn't a seam history.
+pub fn format_name_of(data: &[u8]) -> Option<&str> {
+    if !data.starts_with(MAGIC) || data.len() <= MAGIC.len() {
+        return None;
+    }
+    let length = data[MAGIC.len()] as usize;
+    let start = MAGIC.len() + 1;
+    data.get(start..start + length).and_then(|name| ::std::str::from_utf8(name).ok())
+}
+
+fn removed_from<P: Pixel>(image: &Image<P>, orientation: Orientation, seam: &Vec<usize>) -> RemovedSeam<P> {
+    let pixels = seam.iter().map(|&i| image.pixels[i]).collect();
+    RemovedSeam { orientation: orientation, seam: seam.clone(), pixels: pixels }
+}
+
+/// Puts a removed seam back into the image it was removed from, which it must fit (see `check_seams`).
+fn restore_seam<P: Pixel>(image: &Image<P>, removed: &RemovedSeam<P>) -> Image<P> {
+    let (width, height) = match removed.orientation {
+        Orientation::Vertical => (image.width + 1, image.height),
+        Orientation::Horizontal => (image.width, image.height + 1),
+    };
+    let mut restored = vec!(None; width * height);
+    for (&i, &pixel) in removed.seam.iter().zip(removed.pixels.iter()) {
+        restored[i] = Some(pixel);
+    }
+    // the rest of the pixels fill the gaps in order, along rows for vertical seams and down columns for horizontal ones
+    match removed.orientation {
+        Orientation::Vertical => {
+            let mut remaining = image.pixels.iter();
+            for slot in restored.iter_mut().filter(|slot| slot.is_none()) {
+                *slot = remaining.next().cloned();
+            }
+        },
+        Orientation::Horizontal => for x in 0..width {
+            let mut row = 0;
+            for y in 0..height {
+                if restored[y * width + x].is_none() {
+                    restored[y * width + x] = image.pixels.get(row * width + x).cloned();
+                    row += 1;
+                }
+            }
+        },
+    }
+    Image::new(restored.into_iter().map(|pixel| pixel.expect("seam matches the image")).collect(), width, height)
+}
+
+fn push_u32(data: &mut Vec<u8>, value: usize) {
+    let value = value as u32;
+    data.extend_from_slice(&[value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8]);
+}
+
+fn push_pixels<P: Pixel>(data: &mut Vec<u8>, pixels: &[P]) {
+    let mut channels = Vec::with_capacity(pixels.len() * P::channel_count());
+    for pixel in pixels {
+        pixel.push_channels(&mut channels);
+    }
+    for channel in channels {
+        data.extend_from_slice(&[channel as u8, (channel >> 8) as u8]);
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// The next `count` bytes, if there are that many left; lengths are read from the data, so are checked against
+    /// what's left before anything is allocated for them.
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], Error> {
+        let end = try!(self.position.checked_add(count).ok_or(cut_short()));
+        let bytes = try!(self.data.get(self.position..end).ok_or(cut_short()));
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<usize, Error> {
+        let b = try!(self.bytes(4));
+        Ok((b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24) as usize)
+    }
+
+    fn u32s(&mut self, count: usize) -> Result<Vec<usize>, Error> {
+        let bytes = try!(self.bytes(try!(count.checked_mul(4).ok_or(cut_short()))));
+        Ok(bytes.chunks(4)
+            .map(|b| (b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24) as usize)
+            .collect())
+    }
+
+    fn pixels<P: Pixel>(&mut self, count: usize) -> Result<Vec<P>, Error> {
+        let size = try!(count.checked_mul(P::channel_count() * 2).ok_or(cut_short()));
+        let bytes = try!(self.bytes(size));
+        let channels: Vec<u16> = bytes.chunks(2).map(|b| b[0] as u16 | (b[1] as u16) << 8).collect();
+        Ok(channels.chunks(P::channel_count()).map(P::from_channels).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use carving::{Carver, CarverOptions, Orientation};
+    use lodepng::{Grey, RGB};
+    use super::{format_name_of, SeamHistory};
+    use {noisy_image, Error, Image};
+
+    fn noisy(width: usize, height: usize) -> Image<RGB<u8>> {
+        Image::new(noisy_image(width, height), width, height)
+    }
+
+    fn carve(image: &Image<RGB<u8>>, orientations: &[Orientation]) -> (SeamHistory<RGB<u8>>, Vec<Image<RGB<u8>>>) {
+        let mut carver = Carver::with_options(image.pixels.len(), CarverOptions::default());
+        carver.calculate_energy(image.width, image.height, &image.pixels);
+        let mut history = SeamHistory::new(image);
+        let mut steps = vec!(image.clone());
+        for &orientation in orientations {
+            let seam = carver.find_oriented_seam(history.result.width, history.result.height, orientation);
+            history.remove_seam(&mut carver, orientation, &seam);
+            steps.push(history.result.clone());
+        }
+        (history, steps)
+    }
+
+    #[test]
+    fn rebuilds_the_image_after_any_number_of_removals() {
+        let image = noisy(6, 5);
+        let (history, steps) = carve(&image, &[Orientation::Vertical, Orientation::Horizontal, Orientation::Vertical]);
+
+        for (removed, step) in steps.iter().enumerate() {
+            assert_eq!(&history.image_after(removed), step);
+        }
+        assert_eq!(history.image_after(0), image);
+    }
+
+    #[test]
+    fn finds_the_removals_leaving_a_width() {
+        let orientations = [Orientation::Vertical, Orientation::Horizontal, Orientation::Vertical];
+        let (history, _) = carve(&noisy(6, 5), &orientations);
+
+        assert_eq!(history.removals_to_width(6), Some(0));
+        assert_eq!(history.removals_to_width(5), Some(1));
+        assert_eq!(history.removals_to_width(4), Some(3));
+        assert_eq!(history.removals_to_width(3), None);
+    }
+
+    #[test]
+    fn finds_the_removals_leaving_a_size() {
+        let orientations = [Orientation::Vertical, Orientation::Horizontal, Orientation::Vertical];
+        let (history, steps) = carve(&noisy(6, 5), &orientations);
+
+        for (removed, step) in steps.iter().enumerate() {
+            assert_eq!(history.removals_to_size(step.width, step.height), Some(removed));
+        }
+        assert_eq!(history.removals_to_size(4, 5), None);
+        assert_eq!(history.removals_to_size(6, 4), None);
+    }
+
+    #[test]
+    fn round_trips_through_a_side_file() {
+        let (history, _) = carve(&noisy(5, 4), &[Orientation::Vertical, Orientation::Horizontal]);
+
+        let data = history.to_bytes();
+
+        assert_eq!(format_name_of(&data), Some("rgb8"));
+        assert_eq!(SeamHistory::from_bytes(&data), Ok(history));
+        assert!(SeamHistory::<Grey<u8>>::from_bytes(&data).is_err());
+        assert!(SeamHistory::<RGB<u8>>::from_bytes(&data[..data.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn rejects_lengths_and_seams_which_do_not_fit() {
+        let (history, _) = carve(&noisy(5, 4), &[Orientation::Vertical, Orientation::Horizontal]);
+        let data = history.to_bytes();
+        let header = 8 + 1 + "rgb8".len();
+        let with_u32 = |at: usize, value: u32| {
+            let mut data = data.clone();
+            let bytes = [value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8];
+            data[at..at + 4].copy_from_slice(&bytes);
+            SeamHistory::<RGB<u8>>::from_bytes(&data)
+        };
+        // the first (vertical) seam's length then its indexes, after the 4 x 3 result, the seam count and orientation
+        let first_seam = header + 8 + 4 * 3 * 6 + 4 + 1;
+        let misfit = "seam 1 of the history doesn't fit the 5 x 4 image it was removed from".to_string();
+
+        assert!(with_u32(header, u32::max_value()).is_err());
+        assert!(with_u32(first_seam, u32::max_value()).is_err());
+        assert!(with_u32(first_seam, 3).is_err());
+        assert_eq!(with_u32(first_seam + 4, 5), Err(Error::Format(misfit.clone()))); // in the second row, not the first
+        assert_eq!(with_u32(first_seam + 8, 0), Err(Error::Format(misfit))); // in the first row, not the second
+    }
+}
@@ -0,0 +1,119 @@
+use carving::{Carver, CarverOptions, Orientation};
+use pixel::Pixel;
+use std::cmp;
+use {lazy_remove_indexes_of, subset_by_width_and_height, Error};
+
+/// Widens the image by `count` pixels by duplicating its `count` lowest cost vertical seams, returning the new pixels
+/// (which are `width + count` wide).
+///
+/// Seams are found on a copy of the image, removing each seam from the copy before finding the next so the same seam isn't
+/// picked repeatedly. Each seam pixel is then duplicated in the original, with the inserted pixel being the average of the
+/// seam pixel's left and right neighbours. At most half the current width is inserted per round, so large enlargements are
+/// done in several rounds rather than stretching the whole image uniformly.
+///
+/// An image with no columns has no seams to duplicate, so can't be widened.
+pub fn insert_vertical_seams<P: Pixel>(pixels: &[P], width: usize, height: usize, count: usize,
+        options: CarverOptions) -> Result<Vec<P>, Error> {
+    if height == 0 {
+        return Ok(Vec::new());
+    }
+    if width == 0 && count > 0 {
+        return Err(Error::InvalidSize { width: width, height: height, new_width: width + count, new_height: height });
+    }
+    let mut result = pixels[..(width * height)].to_vec();
+    let mut result_width = width;
+    let mut remaining = count;
+    while remaining > 0 {
+        let round = cmp::min(remaining, cmp::max(result_width / 2, 1));
+        let to_duplicate = lowest_cost_seams(&result, result_width, height, round, options);
+        result = duplicate_pixels(&result, result_width, height, &to_duplicate);
+        result_width += round;
+        remaining -= round;
+    }
+    Ok(result)
+}
+
+/// Returns whether each pixel of the image is part of one of the `count` lowest cost vertical seams.
+fn lowest_cost_seams<P: Pixel>(pixels: &[P], width: usize, height: usize, count: usize, options: CarverOptions)
+        -> Vec<bool> {
+    let mut original_index = (0..(width * height)).collect::<Vec<_>>(); // index into `pixels` of each remaining pixel
+    let mut copy_width = width;
+    let mut in_seam = vec![false; width * height];
+
+    let mut carver = Carver::with_options(width * height, options);
+    carver.calculate_energy(copy_width, height, pixels);
+    for _ in 0..count {
+        let seam = carver.find_seam(copy_width, height);
+        for &pixel in seam.iter() {
+            in_seam[original_index[pixel]] = true;
+        }
+
+        lazy_remove_indexes_of(subset_by_width_and_height(&mut original_index, copy_width, height), &seam);
+        carver.remove_seam(copy_width, height, Orientation::Vertical, &seam);
+        copy_width -= 1;
+    }
+    in_seam
+}
+
+/// Copies the image, inserting a new pixel after each pixel which is marked for duplication. Every row must have the same
+/// number of marked pixels.
+fn duplicate_pixels<P: Pixel>(pixels: &[P], width: usize, height: usize, to_duplicate: &[bool]) -> Vec<P> {
+    let mut result = Vec::with_capacity(pixels.len() + to_duplicate.iter().filter(|&&d| d).count());
+    for y in 0..height {
+        let row = &pixels[(y * width)..((y + 1) * width)];
+        for x in 0..width {
+            result.push(row[x]);
+            if to_duplicate[y * width + x] {
+                let left = row[if x == 0 { x } else { x - 1 }];
+                let right = row[cmp::min(x + 1, width - 1)];
+                result.push(left.average(&right));
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use carving::CarverOptions;
+    use lodepng::RGB;
+    use super::insert_vertical_seams;
+    use Error;
+
+    fn rgb(r: u8, g: u8, b: u8) -> RGB<u8> {
+        RGB { r: r, g: g, b: b }
+    }
+
+    #[test]
+    fn duplicates_distinct_seams_with_averaged_colours() {
+        // every pixel of a 4 x 2 image is on the border, so all have max energy and the leftmost seam is always chosen:
+        // first original column 0, then (after removing it from the copy) original column 1
+        let (a, b, c, d) = (rgb(0, 0, 0), rgb(10, 20, 30), rgb(20, 40, 60), rgb(255, 255, 255));
+        let image = vec!(a, b, c, d, a, b, c, d);
+
+        let widened = insert_vertical_seams(&image, 4, 2, 2, CarverOptions::default()).unwrap();
+
+        // each seam pixel is followed by the average of its left (itself, at the edge) and right neighbours
+        let row = vec!(a, rgb(5, 10, 15), b, rgb(10, 20, 30), c, d);
+        assert_eq!(widened, row.iter().chain(row.iter()).cloned().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn inserts_in_several_rounds_when_more_than_half_the_width_is_requested() {
+        let image = (0..(4 * 3)).map(|i| rgb(i as u8 * 20, 0, 0)).collect::<Vec<_>>();
+
+        let widened = insert_vertical_seams(&image, 4, 3, 7, CarverOptions::default()).unwrap();
+
+        assert_eq!(widened.len(), (4 + 7) * 3);
+    }
+
+    #[test]
+    fn can_only_widen_images_with_columns() {
+        let image = vec!(rgb(1, 2, 3); 2);
+
+        assert_eq!(insert_vertical_seams(&image[..0], 0, 2, 3, CarverOptions::default()),
+            Err(Error::InvalidSize { width: 0, height: 2, new_width: 3, new_height: 2 }));
+        assert_eq!(insert_vertical_seams(&image[..0], 2, 0, 3, CarverOptions::default()), Ok(vec!()));
+        assert_eq!(insert_vertical_seams(&image, 2, 1, 0, CarverOptions::default()), Ok(image.clone()));
+    }
+}
@@ -0,0 +1,449 @@
+#![cfg_attr(feature = "bench", feature(test))] // benchmarks need nightly, so are only built with --features bench
+
+extern crate crossbeam;
+extern crate graph;
+extern crate lodepng;
+extern crate num_cpus;
+#[cfg(all(test, feature = "bench"))] extern crate test;
+
+pub use error::Error;
+
+use carving::{Carver, CarverOptions, Orientation};
+use lodepng::RGB;
+use pixel::Pixel;
+use std::cmp;
+use std::usize;
+use visualisation::Visualisation;
+
+mod bmp;
+pub mod batch;
+pub mod carving;
+pub mod energy;
+pub mod error;
+pub mod geometry;
+pub mod graph_cut;
+pub mod history;
+pub mod insertion;
+pub mod ordering;
+pub mod picture;
+pub mod pixel;
+pub mod removal_index;
+pub mod video;
+mod pnm;
+pub mod visualisation;
+
+/// An image which owns its pixels, laid out row by row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image<P> {
+    pub pixels: Vec<P>,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl<P: Pixel> Image<P> {
+    pub fn new(pixels: Vec<P>, width: usize, height: usize) -> Image<P> {
+        assert_eq!(pixels.len(), width * height, "image must have exactly width * height pixels");
+        Image { pixels: pixels, width: width, height: height }
+    }
+}
+
+/// Resizes the image to `new_width` x `new_height`, removing its lowest cost seams (vertical ones first) to shrink it
+/// and duplicating them to enlarge it. When reducing both dimensions, `ordering::optimal_order` finds a cheaper order
+/// of removals, which can then be carried out with `remove_seam`. Neither the image nor the result can be empty.
+pub fn retarget<P: Pixel>(image: &Image<P>, new_width: usize, new_height: usize, options: CarverOptions)
+        -> Result<Image<P>, Error> {
+    retarget_with(&mut Carver::with_options(0, options), image, new_width, new_height, options)
+}
+
+/// Like `retarget`, but removes seams with the given carver (which must have been created with `options`, and has
+/// any mask or guide it had dropped), so it can be reused for image after image.
+pub fn retarget_with<P: Pixel>(carver: &mut Carver, image: &Image<P>, new_width: usize, new_height: usize,
+        options: CarverOptions) -> Result<Image<P>, Error> {
+    if image.width == 0 || image.height == 0 || new_width == 0 || new_height == 0 {
+        return Err(Error::InvalidSize { width: image.width, height: image.height, new_width: new_width,
+            new_height: new_height });
+    }
+    let mut image = image.clone();
+    if new_width > image.width {
+        image = try!(widen(&image, new_width - image.width, options));
+    }
+    if new_height > image.height {
+        // inserting vertical seams in the transposed image inserts horizontal seams in the image
+        let height_increase = new_height - image.height;
+        image = transpose(&try!(widen(&transpose(&image), height_increase, options)));
+    }
+
+    carver.reserve(image.pixels.len());
+    carver.set_mask(Vec::new());
+    carver.set_guide(Vec::new(), 0);
+    carver.calculate_energy(image.width, image.height, &image.pixels);
+    for &(orientation, target) in [(Orientation::Vertical, new_width), (Orientation::Horizontal, new_height)].iter() {
+        while breadth_of(&image, orientation) > target {
+            let seam = carver.find_oriented_seam(image.width, image.height, orientation);
+            remove_seam(carver, &mut image, orientation, &seam);
+        }
+    }
+    Ok(image)
+}
+
+/// Removes `seam` (as found by `carver`) from the image, and updates the carver to match. Removing an empty seam (as
+/// found in an empty image) does nothing.
+pub fn remove_seam<P: Pixel>(carver: &mut Carver, image: &mut Image<P>, orientation: Orientation, seam: &Vec<usize>) {
+    if seam.is_empty() {
+        return;
+    }
+    let (width, height) = lazy_remove_seam(&mut image.pixels[..], image.width, image.height, orientation, seam);
+    image.pixels.truncate(width * height);
+    carver.remove_seam(image.width, image.height, orientation, seam);
+    image.width = width;
+    image.height = height;
+}
+
+/// Outlines the `count` lowest cost seams of the given orientation in red without removing them, as they would be
+/// found by removing them one after the other (stopping early if the image runs out of room).
+pub fn preview_seams<P: Pixel>(image: &Image<P>, orientation: Orientation, count: usize, options: CarverOptions)
+        -> Image<P> {
+    let mut preview = image.clone();
+    let mut remaining = image.clone();
+    let mut original_index = (0..image.pixels.len()).collect::<Vec<_>>(); // index into `image` of each remaining pixel
+    let mut carver = Carver::with_options(image.pixels.len(), options);
+    carver.calculate_energy(image.width, image.height, &image.pixels);
+    for _ in 0..count {
+        if remaining.pixels.is_empty() || breadth_of(&remaining, orientation) == 1 {
+            break;
+        }
+        let seam = carver.find_oriented_seam(remaining.width, remaining.height, orientation);
+        draw_seam(&mut preview, &seam.iter().map(|&pixel| original_index[pixel]).collect::<Vec<_>>());
+
+        lazy_remove_seam(&mut original_index[..], remaining.width, remaining.height, orientation, &seam);
+        remove_seam(&mut carver, &mut remaining, orientation, &seam);
+        original_index.truncate(remaining.pixels.len());
+    }
+    preview
+}
+
+/// Outlines the given seam of the image in red (or whatever grey red becomes, for grey images).
+pub fn draw_seam<P: Pixel>(image: &mut Image<P>, seam: &[usize]) {
+    for &pixel in seam {
+        image.pixels[pixel] = P::from_rgb8(RGB { r: 255, g: 0, b: 0 });
+    }
+}
+
+/// A `width` x `height` copy of the image (which must be no bigger), with the space to its right and below it filled
+/// with `fill`.
+pub fn pad<P: Pixel>(image: &Image<P>, width: usize, height: usize, fill: P) -> Image<P> {
+    assert!(image.width <= width && image.height <= height, "can't pad an image to a smaller size");
+    let mut pixels = Vec::with_capacity(width * height);
+    // by row number rather than chunks of the pixels, so an image 0 pixels wide still gets padded rows
+    for y in 0..image.height {
+        pixels.extend(image.pixels[(y * image.width)..((y + 1) * image.width)].iter().cloned());
+        pixels.extend((image.width..width).map(|_| fill));
+    }
+    pixels.extend((0..((height - image.height) * width)).map(|_| fill));
+    Image::new(pixels, width, height)
+}
+
+/// An image of the same size as the given one, displaying the energy of each of its pixels.
+pub fn energy_map<P: Pixel>(image: &Image<P>, options: CarverOptions, visualisation: Visualisation) -> Image<P> {
+    let mut carver = Carver::with_options(image.pixels.len(), options);
+    carver.calculate_energy(image.width, image.height, &image.pixels);
+    energy_image(&carver, image.width, image.height, visualisation)
+}
+
+/// An image displaying the energy the carver has calculated for each pixel of a `width` x `height` image.
+pub fn energy_image<P: Pixel>(carver: &Carver, width: usize, height: usize, visualisation: Visualisation) -> Image<P> {
+    let energy = carver.energy[..(width * height)].iter().map(|&energy| energy as i64).collect::<Vec<_>>();
+    Image::new(visualisation.colours(&energy).into_iter().map(P::from_rgb8).collect(), width, height)
+}
+
+/// Removes an object along whichever direction it's narrowest in, so as few seams as possible are needed.
+pub fn removal_orientation(marked: &[usize], width: usize) -> Orientation {
+    let (mut min_x, mut max_x, mut min_y, mut max_y) = (usize::MAX, 0, usize::MAX, 0);
+    for &i in marked {
+        min_x = cmp::min(min_x, i % width);
+        max_x = cmp::max(max_x, i % width);
+        min_y = cmp::min(min_y, i / width);
+        max_y = cmp::max(max_y, i / width);
+    }
+    if marked.is_empty() || max_x - min_x <= max_y - min_y {
+        Orientation::Vertical
+    } else {
+        Orientation::Horizontal
+    }
+}
+
+/// The size of the image across seams of the given orientation, i.e. how many seams it has room for.
+fn breadth_of<P>(image: &Image<P>, orientation: Orientation) -> usize {
+    match orientation {
+        Orientation::Vertical => image.width,
+        Orientation::Horizontal => image.height,
+    }
+}
+
+fn widen<P: Pixel>(image: &Image<P>, count: usize, options: CarverOptions) -> Result<Image<P>, Error> {
+    let pixels = try!(insertion::insert_vertical_seams(&image.pixels, image.width, image.height, count, options));
+    Ok(Image::new(pixels, image.width + count, image.height))
+}
+
+fn transpose<P: Pixel>(image: &Image<P>) -> Image<P> {
+    let (width, height) = (image.width, image.height);
+    let pixels = (0..(width * height)).map(|i| image.pixels[(i % height) * width + i / height]).collect();
+    Image::new(pixels, height, width)
+}
+
+fn subset_by_width_and_height<A>(slice: &mut [A], width: usize, height: usize) -> &mut [A] {
+    &mut slice[..(width * height)]
+}
+
+/// For each index `A` of `to_remove` into `slice`, set `slice[A] = slice[A + 1]`. The last `to_remove.len()` items in
+/// `slice` will contain junk after this. Runs in linear time and requires `to_remove` to be sorted w.r.t. `slice`.
+fn lazy_remove_indexes_of<A: Clone>(slice: &mut [A], to_remove: &Vec<usize>) {
+    for (offset, start) in to_remove.iter().enumerate() {
+        let finish = if offset < to_remove.len() - 1 {
+            to_remove[offset + 1]
+        } else {
+            slice.len()
+        };
+        for idx in (start + 1)..finish {
+            let src = idx;
+            let dst = idx - offset - 1;
+            // move element at src to dst; we don't care about what happens to the dst element, which we're deleting
+            slice.swap(src, dst);
+        }
+    }
+}
+
+/// Lazily removes the given seam from the first `width * height` items of `slice` (see `lazy_remove_indexes_of` and
+/// `lazy_remove_horizontal_seam`), returning the new width and height of the image.
+fn lazy_remove_seam<A: Clone>(slice: &mut [A], width: usize, height: usize, orientation: Orientation,
+        seam: &Vec<usize>) -> (usize, usize) {
+    let image = subset_by_width_and_height(slice, width, height);
+    match orientation {
+        Orientation::Vertical => {
+            lazy_remove_indexes_of(image, seam);
+            (width - 1, height)
+        },
+        Orientation::Horizontal => {
+            lazy_remove_horizontal_seam(image, width, height, seam);
+            (width, height - 1)
+        },
+    }
+}
+
+/// For each column of the image, remove the pixel at that column's index in `seam` by shifting every pixel below it up
+/// one row, so the first `width * (height - 1)` items of `slice` are the image with the seam removed (and the last row
+/// contains junk). Requires `seam` to contain exactly 1 index per column, in column order.
+fn lazy_remove_horizontal_seam<A: Clone>(slice: &mut [A], width: usize, height: usize, seam: &Vec<usize>) {
+    for (column, &start) in seam.iter().enumerate() {
+        debug_assert_eq!(start % width, column);
+        let mut idx = start;
+        while idx + width < width * height {
+            // move the pixel below up into this row; the pixel being removed sinks to the (junk) last row
+            slice.swap(idx, idx + width);
+            idx += width;
+        }
+    }
+}
+
+/// A reproducible image of random pixels, for the tests of every module.
+#[cfg(test)]
+fn noisy_image(width: usize, height: usize) -> Vec<RGB<u8>> {
+    let mut state = 12345u32;
+    let mut next = || {
+        state = state.wrapping_mul(1103515245).wrapping_add(12345);
+        (state >> 16) as u8
+    };
+    (0..(width * height)).map(|_| RGB { r: next(), g: next(), b: next() }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use carving::{Carver, CarverOptions, Orientation, SeamCost};
+    use energy::{BorderPolicy, Energy};
+    use lodepng::RGB;
+    use super::{energy_map, lazy_remove_horizontal_seam, lazy_remove_indexes_of, noisy_image, pad, preview_seams,
+        removal_orientation, retarget, retarget_with, transpose, Error, Image};
+    use visualisation::Visualisation;
+
+    /// A 6 x 4 image which gets brighter towards the right.
+    fn gradient_image() -> Image<RGB<u8>> {
+        let pixels = (0..(6 * 4)).map(|i| {
+            let v = ((i % 6) * 40 + (i / 6) * 7) as u8;
+            RGB { r: v, g: v, b: v }
+        }).collect();
+        Image::new(pixels, 6, 4)
+    }
+
+    #[test]
+    fn lazy_remove_indexes_of_works_correctly() {
+        let mut vec = (0..11).collect::<Vec<u32>>();
+        let to_remove = vec!(1, 3, 7);
+
+        lazy_remove_indexes_of(&mut vec[..], &to_remove);
+        let vec_len = vec.len();
+        vec.truncate(vec_len - to_remove.len()); // only the first 7 elements of vec are valid now
+
+        // 0 1 2 3 4 5 6 7 8 9 10 11 gets turned into
+        // 0   2   4 5 6   8 9 10 11
+        assert_eq!(vec, vec!(0, 2, 4, 5, 6, 8, 9, 10));
+    }
+
+    #[test]
+    fn lazy_remove_indexes_of_works_correctly_on_edges() {
+        let mut vec = (0..5).collect::<Vec<u32>>();
+        let to_remove = vec!(0, 5);
+
+        lazy_remove_indexes_of(&mut vec[..], &to_remove);
+        let vec_len = vec.len();
+        vec.truncate(vec_len - to_remove.len()); // only the first 3 elements of vec are valid now
+
+        // 0 1 2 3 4 gets turned into
+        //   1 2 3
+        assert_eq!(vec, vec!(1, 2, 3));
+    }
+
+    #[test]
+    fn lazy_remove_horizontal_seam_works_correctly() {
+        let mut vec = (0..12).collect::<Vec<u32>>();
+        let seam = vec!(3, 7, 5); // 1 per column of a 3 x 4 image
+
+        lazy_remove_horizontal_seam(&mut vec[..], 3, 4, &seam);
+        vec.truncate(3 * 3); // only the first 3 rows are valid now
+
+        // 0  1  2          0  1  2
+        // 3  4  5  becomes 6  4  8
+        // 6  7  8          9 10 11
+        // 9 10 11
+        assert_eq!(vec, vec!(0, 1, 2, 6, 4, 8, 9, 10, 11));
+    }
+
+    #[test]
+    fn removes_objects_along_their_narrowest_direction() {
+        // in a 10 pixel wide image, a 2 x 3 object (columns 4-5, rows 1-3) and a 3 x 1 object (columns 2-4, row 6)
+        assert_eq!(removal_orientation(&[14, 15, 24, 25, 34, 35], 10), Orientation::Vertical);
+        assert_eq!(removal_orientation(&[62, 63, 64], 10), Orientation::Horizontal);
+    }
+
+    #[test]
+    fn transposes_images() {
+        let image = Image::new((0..6).map(|v| RGB { r: v as u8, g: 0, b: 0 }).collect(), 3, 2);
+
+        let transposed = transpose(&image);
+
+        // 0 1 2          0 3
+        // 3 4 5 becomes  1 4
+        //                2 5
+        assert_eq!((transposed.width, transposed.height), (2, 3));
+        assert_eq!(transposed.pixels.iter().map(|p| p.r).collect::<Vec<_>>(), vec!(0, 3, 1, 4, 2, 5));
+        assert_eq!(transpose(&transposed), image);
+    }
+
+    #[test]
+    fn retargets_to_smaller_and_larger_sizes() {
+        let image = gradient_image();
+
+        for &(width, height) in [(4, 3), (9, 2), (3, 7), (8, 5), (6, 4)].iter() {
+            let retargeted = retarget(&image, width, height, CarverOptions::default()).unwrap();
+
+            assert_eq!((retargeted.width, retargeted.height), (width, height));
+            assert_eq!(retargeted.pixels.len(), width * height);
+        }
+    }
+
+    #[test]
+    fn retargets_tiny_images() {
+        let options = [
+            CarverOptions::default(),
+            CarverOptions { seam_cost: SeamCost::Forward, ..CarverOptions::default() },
+            CarverOptions { border: BorderPolicy::Wrapped, energy: Energy::Sobel, ..CarverOptions::default() },
+            CarverOptions { border: BorderPolicy::Mirrored, energy: Energy::Entropy, ..CarverOptions::default() },
+        ];
+        let sizes = [(1, 4, 1, 2), (1, 4, 3, 4), (4, 1, 2, 1), (4, 1, 4, 3), (2, 2, 1, 1), (2, 2, 3, 3), (1, 1, 1, 1)];
+        for &options in options.iter() {
+            for &(width, height, new_width, new_height) in sizes.iter() {
+                let image = Image::new((0..(width * height)).map(|v| RGB { r: v as u8 * 60, g: 0, b: 0 }).collect(),
+                    width, height);
+
+                let retargeted = retarget(&image, new_width, new_height, options).unwrap();
+
+                assert_eq!((retargeted.width, retargeted.height), (new_width, new_height));
+            }
+        }
+    }
+
+    #[test]
+    fn reuses_a_carver_for_differently_sized_images() {
+        let small = gradient_image();
+        let large = Image::new(noisy_image(9, 7), 9, 7);
+        let mut carver = Carver::with_options(0, CarverOptions::default());
+
+        for &(ref image, width, height) in [(&small, 4, 3), (&large, 5, 6), (&small, 3, 2)].iter() {
+            assert_eq!(retarget_with(&mut carver, image, width, height, CarverOptions::default()),
+                retarget(image, width, height, CarverOptions::default()));
+        }
+    }
+
+    #[test]
+    fn refuses_to_retarget_to_or_from_empty_images() {
+        let image = Image::new(vec![RGB { r: 0u8, g: 0, b: 0 }; 4], 2, 2);
+        let empty = Image::new(Vec::<RGB<u8>>::new(), 0, 3);
+
+        assert_eq!(retarget(&image, 0, 2, CarverOptions::default()),
+            Err(Error::InvalidSize { width: 2, height: 2, new_width: 0, new_height: 2 }));
+        assert!(retarget(&empty, 1, 1, CarverOptions::default()).is_err());
+    }
+
+    #[test]
+    fn previews_distinct_seams_without_removing_them() {
+        let image = gradient_image();
+        let red = RGB { r: 255, g: 0, b: 0 };
+
+        let preview = preview_seams(&image, Orientation::Vertical, 2, CarverOptions::default());
+
+        assert_eq!((preview.width, preview.height), (6, 4));
+        for row in preview.pixels.chunks(6) {
+            assert_eq!(row.iter().filter(|&&p| p == red).count(), 2);
+        }
+        // there's only room for 3 horizontal seams, so asking for more previews every row
+        let preview = preview_seams(&image, Orientation::Horizontal, 10, CarverOptions::default());
+        assert_eq!(preview.pixels.iter().filter(|&&p| p == red).count(), 3 * 6);
+    }
+
+    #[test]
+    fn previews_and_maps_empty_images() {
+        for &(width, height) in [(0, 0), (0, 3), (3, 0)].iter() {
+            let empty = Image::new(Vec::<RGB<u8>>::new(), width, height);
+
+            for &orientation in [Orientation::Vertical, Orientation::Horizontal].iter() {
+                assert_eq!(preview_seams(&empty, orientation, 2, CarverOptions::default()), empty);
+            }
+            assert_eq!(energy_map(&empty, CarverOptions::default(), Visualisation::default()), empty);
+            assert_eq!(pad(&empty, width, height, RGB { r: 0, g: 0, b: 0 }), empty);
+        }
+        let fill = RGB { r: 9u8, g: 0, b: 0 };
+        assert_eq!(pad(&Image::new(Vec::new(), 0, 3), 2, 4, fill), Image::new(vec![fill; 2 * 4], 2, 4));
+    }
+
+    #[test]
+    fn pads_images_to_the_right_and_below() {
+        let image = Image::new((1..5).map(|v| RGB { r: v as u8, g: 0, b: 0 }).collect(), 2, 2);
+
+        let padded = pad(&image, 3, 3, RGB { r: 0, g: 0, b: 0 });
+
+        // 1 2 0
+        // 3 4 0
+        // 0 0 0
+        assert_eq!(padded.pixels.iter().map(|p| p.r).collect::<Vec<_>>(), vec!(1, 2, 0, 3, 4, 0, 0, 0, 0));
+        assert_eq!(pad(&image, 2, 2, RGB { r: 0, g: 0, b: 0 }), image);
+    }
+
+    #[test]
+    fn maps_energy_of_every_pixel() {
+        let map = energy_map(&gradient_image(), CarverOptions::default(), Visualisation::default());
+
+        // border pixels have the most energy with the default options, and the gradient is the same everywhere else
+        assert_eq!((map.width, map.height), (6, 4));
+        assert_eq!(map.pixels[0], RGB { r: 255, g: 255, b: 255 });
+        assert_eq!(map.pixels[6 + 1], RGB { r: 0, g: 0, b: 0 });
+    }
+}
@@ -1,14 +1,29 @@
 extern crate getopts;
 extern crate lodepng;
+extern crate seam_carving;
 
-use carving::Carver;
-use getopts::Options;
+use getopts::{Matches, Options};
+use lodepng::{Grey, GreyAlpha, RGB, RGBA};
+use seam_carving::batch;
+use seam_carving::carving::{Carver, CarverOptions, Mark, Orientation, SeamCost};
+use seam_carving::energy::{BorderPolicy, Energy};
+use seam_carving::geometry::{self, Geometry};
+use seam_carving::graph_cut::{self, Connectivity};
+use seam_carving::history::{self, SeamHistory};
+use seam_carving::picture::{self, Format, Picture, Pixels};
+use seam_carving::pixel::Pixel;
+use seam_carving::removal_index::RemovalIndex;
+use seam_carving::video;
+use seam_carving::visualisation::{self, ColourMap, Scaling, Visualisation};
+use seam_carving::{insertion, ordering, retarget, Error, Image};
+use std::cmp;
 use std::env;
-use std::path::Path;
-use std::convert::AsMut;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use std::process;
-
-mod carving;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
@@ -19,14 +34,7 @@ fn main() {
         print!("{}", opts.usage(&brief));
     }
 
-    let mut opts = Options::new();
-    opts.optopt("o", "output", "path to output the resulting image", "OUTPUT-FILE");
-    opts.optflag("e", "energy", "convert the given image to a display of its energy");
-    opts.optflag("p", "preview", "outline the next seam that would be removed in bright red (don't remove it)");
-    opts.optopt("W", "width-reduction", "the number of pixels to reduce the width by", "WIDTH-COUNT");
-    opts.optflag("v", "verbose", "print out energy and discovered seams");
-    opts.optflag("h", "help", "print this usage information");
-
+    let opts = options();
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => m,
         Err(f) => {
@@ -47,146 +55,980 @@ fn main() {
         print_usage(&program, opts);
         process::exit(1);
     };
-    let output_energy = matches.opt_present("e");
-    let preview_next_seam = matches.opt_present("p");
-    let width_reduction: u32 = matches.opt_str("W").unwrap_or("0".to_owned())
-        .parse().ok().expect("-W argument must be a number");
-    let verbose_mode = matches.opt_present("v");
+    let width_reduction: usize = number_option(&matches, "width-reduction", 0);
+    let height_reduction: usize = number_option(&matches, "height-reduction", 0);
+    let width_increase: usize = number_option(&matches, "width-increase", 0);
+    let resize = parse_geometry(&matches);
+    let defaults = CarverOptions::default();
+    let seam_cost = match matches.opt_str("c") {
+        None => defaults.seam_cost,
+        Some(name) => match SeamCost::from_name(&name) {
+            Some(seam_cost) => seam_cost,
+            None => {
+                println!("Invalid arguments: unknown seam cost {}", name);
+                print_usage(&program, opts);
+                process::exit(1);
+            },
+        },
+    };
+    let energy = match matches.opt_str("E") {
+        None => defaults.energy,
+        Some(name) => match Energy::from_name(&name) {
+            Some(energy) => energy,
+            None => {
+                println!("Invalid arguments: unknown energy function {}", name);
+                print_usage(&program, opts);
+                process::exit(1);
+            },
+        },
+    };
+    if seam_cost == SeamCost::Forward && matches.opt_present("E") {
+        println!("Invalid arguments: -E can't be combined with -c forward, which measures colour differences directly");
+        process::exit(1);
+    }
+    let border = match matches.opt_str("b") {
+        None => defaults.border,
+        Some(name) => match BorderPolicy::from_name(&name) {
+            Some(border) => border,
+            None => {
+                println!("Invalid arguments: unknown border policy {}", name);
+                print_usage(&program, opts);
+                process::exit(1);
+            },
+        },
+    };
+    let threads: usize = number_option(&matches, "threads", defaults.threads);
+    let carver_options = CarverOptions { seam_cost: seam_cost, energy: energy, border: border, threads: threads };
+    let graph_cut = match matches.opt_str("graph-cut") {
+        None => None,
+        Some(name) => match Connectivity::from_name(&name) {
+            Some(connectivity) => Some(connectivity),
+            None => {
+                println!("Invalid arguments: unknown graph cut connectivity {}", name);
+                print_usage(&program, opts);
+                process::exit(1);
+            },
+        },
+    };
+    let scaling = match matches.opt_str("energy-scale") {
+        None => Scaling::Linear,
+        Some(name) => match Scaling::from_name(&name) {
+            Some(scaling) => scaling,
+            None => {
+                println!("Invalid arguments: unknown energy scale {}", name);
+                print_usage(&program, opts);
+                process::exit(1);
+            },
+        },
+    };
+    let colour_map = match matches.opt_str("colour-map") {
+        None => ColourMap::Grey,
+        Some(name) => match ColourMap::from_name(&name) {
+            Some(colour_map) => colour_map,
+            None => {
+                println!("Invalid arguments: unknown colour map {}", name);
+                print_usage(&program, opts);
+                process::exit(1);
+            },
+        },
+    };
 
-    let mut bitmap = match lodepng::decode24_file(input_img_path) {
-        Ok(bitmap) => bitmap,
-        Err(reason) => panic!("Could not load {}, because: {}", input_img_path.display(), reason),
+    let settings = Settings {
+        output_energy: matches.opt_present("e"),
+        preview_next_seam: matches.opt_present("p"),
+        width_reduction: width_reduction,
+        height_reduction: height_reduction,
+        width_increase: width_increase,
+        resize: resize,
+        target: matches.opt_str("t"),
+        verbose_mode: matches.opt_present("v"),
+        carver_options: carver_options,
+        graph_cut: graph_cut,
+        protect_mask_path: matches.opt_str("protect"),
+        remove_mask_path: matches.opt_str("remove"),
+        frames_directory: matches.opt_str("frames"),
+        pad_frames: matches.opt_present("pad-frames"),
+        visualisation: Visualisation { scaling: scaling, colour_map: colour_map },
+        energy_dump_path: matches.opt_str("dump-energy"),
+        distances_dump_path: matches.opt_str("dump-distances"),
+        history_path: matches.opt_str("history"),
+        removal_order_dump_path: matches.opt_str("dump-removal-order"),
     };
+    let masked = settings.protect_mask_path.is_some() || settings.remove_mask_path.is_some();
+    if masked && (width_increase > 0 || settings.target.is_some()) {
+        println!("Invalid arguments: --protect and --remove can't be combined with --width-increase or --target");
+        process::exit(1);
+    }
+    if width_increase > 0 && (width_reduction > 0 || settings.target.is_some()) {
+        println!("Invalid arguments: --width-increase can't be combined with -W or --target");
+        process::exit(1);
+    }
+    if resize.is_some() && (width_reduction > 0 || height_reduction > 0 || width_increase > 0 ||
+            settings.target.is_some()) {
+        println!("Invalid arguments: --width, --height, --scale and --aspect can't be combined with -W, -H, -I or \
+            --target");
+        process::exit(1);
+    }
+    if settings.target.is_some() && (width_reduction > 0 || height_reduction > 0) {
+        println!("Invalid arguments: --target can't be combined with -W or -H");
+        process::exit(1);
+    }
 
-    println!("Decoded {} x {} image at {}", bitmap.width, bitmap.height,
-        input_img_path.to_str().expect("path should be valid"));
+    let output_format = match matches.opt_str("f") {
+        None => None,
+        Some(name) => match Format::from_name(&name) {
+            Some(format) => Some(format),
+            None => {
+                println!("Invalid arguments: unknown image format {}", name);
+                process::exit(1);
+            },
+        },
+    };
+    if let Some(size) = matches.opt_str("replay") {
+        let (width, height) = match (parse_dimensions(&size), size.parse()) {
+            (Some((width, height)), _) => (width, Some(height)),
+            (None, Ok(width)) => (width, None),
+            (None, Err(_)) => {
+                println!("Invalid arguments: --replay must be WIDTH or WIDTHxHEIGHT, not {}", size);
+                process::exit(1);
+            },
+        };
+        let output_img_str = match matches.opt_str("o") {
+            Some(output_img_str) => output_img_str,
+            None => {
+                println!("Invalid arguments: --replay needs -o to save the replayed image");
+                process::exit(1);
+            },
+        };
+        let output_img_path = Path::new(&output_img_str);
+        let picture = replay(input_img_path, width, height);
+        let output_format = output_format.or_else(|| Format::from_path(output_img_path)).unwrap_or(Format::Png);
+        if let Err(e) = picture::save(output_img_path, output_format, &picture) {
+            exit_with_error(format!("Failed to save {:?} image to {} because: {}", output_format, output_img_str, e));
+        }
+        println!("Saved {} x {} image to {}", picture.width, picture.height, output_img_str);
+        return;
+    }
 
-    if verbose_mode { println!("Calculating pixel energies..."); }
-    let mut carver = Carver::new(bitmap.buffer.len());
-    carver.calculate_energy(bitmap.width, bitmap.height, bitmap.buffer.as_mut());
-    let mut seam = carver.find_seam(bitmap.width, bitmap.height);
+    if matches.opt_present("video") {
+        if settings.target.is_some() || resize.is_some() || width_increase > 0 || masked ||
+                settings.graph_cut.is_some() || settings.output_energy || settings.preview_next_seam ||
+                settings.frames_directory.is_some() || settings.history_path.is_some() ||
+                settings.energy_dump_path.is_some() || settings.distances_dump_path.is_some() ||
+                settings.removal_order_dump_path.is_some() || matches.opt_present("batch") {
+            println!("Invalid arguments: --video can only be combined with -W, -H, --coherence, -c, -E, -b, -j, -f and \
+                -o");
+            process::exit(1);
+        }
+        let output_directory = match matches.opt_str("o") {
+            Some(output_directory) => output_directory,
+            None => {
+                println!("Invalid arguments: --video needs -o to give the directory to save the carved frames to");
+                process::exit(1);
+            },
+        };
+        let coherence = number_option(&matches, "coherence", video::DEFAULT_COHERENCE);
+        carve_video(input_img_path, Path::new(&output_directory), &settings, coherence, output_format);
+        return;
+    }
 
-    print!("Reducing width of image by {} pixels", width_reduction);
-    for _ in 0..width_reduction {
-        if verbose_mode {
-            println!("");
-            println!("Will remove seam: {:?}", seam);
-            println!("As energy:        {:?}", seam.iter().map(|seam_pixel_index|
-                carver.energy[*seam_pixel_index]).collect::<Vec<_>>());
+    if let Some(size) = matches.opt_str("batch") {
+        if settings.target.is_some() || resize.is_some() || width_reduction > 0 || height_reduction > 0 ||
+                width_increase > 0 || masked ||
+                settings.graph_cut.is_some() || settings.output_energy || settings.preview_next_seam ||
+                settings.frames_directory.is_some() || settings.history_path.is_some() ||
+                settings.energy_dump_path.is_some() || settings.distances_dump_path.is_some() ||
+                settings.removal_order_dump_path.is_some() {
+            println!("Invalid arguments: --batch can only be combined with -c, -E, -b, -j, -f and -o");
+            process::exit(1);
         }
+        let size = match batch::Size::from_spec(&size) {
+            Some(size) => size,
+            None => {
+                println!("Invalid arguments: --batch size must be WIDTHxHEIGHT or a percentage such as 75%, not {}",
+                    size);
+                process::exit(1);
+            },
+        };
+        let output_directory = match matches.opt_str("o") {
+            Some(output_directory) => output_directory,
+            None => {
+                println!("Invalid arguments: --batch needs -o to give the directory to save the carved images to");
+                process::exit(1);
+            },
+        };
+        carve_batch(&matches.free[0], Path::new(&output_directory), size, &settings, output_format);
+        return;
+    }
 
-        lazy_remove_indexes_of(subset_by_width_and_height(bitmap.buffer.as_mut(), bitmap.width, bitmap.height), &seam);
-        bitmap.width = bitmap.width - 1;
+    let (picture, input_format) = match picture::load(input_img_path) {
+        Ok(loaded) => loaded,
+        Err(reason) => exit_with_error(format!("Could not load {}, because: {}", input_img_path.display(), reason)),
+    };
 
-        if verbose_mode { println!("Recalculating pixel energies..."); }
-        carver.calculate_energy(bitmap.width, bitmap.height,
-            subset_by_width_and_height(bitmap.buffer.as_mut(), bitmap.width, bitmap.height));
-        if verbose_mode { println!("Finding next seam..."); }
-        seam = carver.find_seam(bitmap.width, bitmap.height);
+    println!("Decoded {} x {} image at {}", picture.width, picture.height,
+        input_img_path.display());
 
-        if !verbose_mode {
-            use std::io::{self, Write};
-            print!(".");
-            io::stdout().flush().unwrap();
+    // carve the pixels in whatever format they were decoded in, so they can be saved the same way
+    let Picture { pixels, width, height } = picture;
+    macro_rules! carve_each_format {
+        ($($format:ident),*) => {
+            match pixels {
+                $(Pixels::$format(pixels) => {
+                    let image = carve(Image::new(pixels, width, height), &settings);
+                    Picture { pixels: Pixels::$format(image.pixels), width: image.width, height: image.height }
+                },)*
+            }
         }
     }
+    let picture = carve_each_format!(Grey, Grey16, GreyAlpha, GreyAlpha16, Rgb, Rgb16, Rgba, Rgba16);
+
+    match matches.opt_str("o") {
+        Some(output_img_str) => {
+            let output_img_path = Path::new(&output_img_str);
+            let output_format = output_format.or_else(|| Format::from_path(output_img_path)).unwrap_or(input_format);
+            if let Err(e) = picture::save(output_img_path, output_format, &picture) {
+                exit_with_error(format!("Failed to save {:?} image to {} because: {}", output_format, output_img_str,
+                    e));
+            }
+
+            println!("Saved output image to {}", output_img_str);
+        },
+        None => println!("Not saving output image; specify -o flag if you want to save the result"),
+    };
+}
+
+/// The command line options.
+fn options() -> Options {
+    let mut opts = Options::new();
+    opts.optopt("o", "output", "path to output the resulting image", "OUTPUT-FILE");
+    opts.optflag("e", "energy", "convert the given image to a display of its energy");
+    opts.optopt("", "energy-scale", "how to scale energy (and distances) for display: linear (the default), log or \
+        histogram (equalised)", "SCALE");
+    opts.optopt("", "colour-map", "the colours to display energy (and distances) in: grey (the default), viridis or \
+        heat", "MAP");
+    opts.optopt("", "dump-energy", "save the energy of each pixel of the result as CSV (if the file ends in .csv) or \
+        as an image", "FILE");
+    opts.optopt("", "dump-distances", "save the cost of the cheapest path to each pixel of the result (as found when \
+        searching for the next seam) as CSV (if the file ends in .csv) or as an image", "FILE");
+    opts.optopt("", "dump-removal-order", "save when each pixel of the image would be removed by removing vertical \
+        seams until 1 column is left, as CSV (if the file ends in .csv) or as an image", "FILE");
+    opts.optflag("p", "preview", "outline the next seam that would be removed in bright red (don't remove it)");
+    opts.optopt("W", "width-reduction", "the number of pixels to reduce the width by", "WIDTH-COUNT");
+    opts.optopt("H", "height-reduction", "the number of pixels to reduce the height by", "HEIGHT-COUNT");
+    opts.optopt("I", "width-increase", "the number of pixels to increase the width by, by duplicating low energy seams",
+        "WIDTH-COUNT");
+    opts.optopt("", "width", "resize the image to the given width, removing or inserting seams (the height is left as \
+        it is unless --height or --aspect is given)", "WIDTH");
+    opts.optopt("", "height", "resize the image to the given height, removing or inserting seams", "HEIGHT");
+    opts.optopt("", "scale", "resize the image to the given percentage of its size, e.g. 75%", "PERCENT");
+    opts.optopt("", "aspect", "resize the image to the given aspect ratio, e.g. 16:9: from --width or --height if one \
+        is given, otherwise by shrinking whichever dimension is too big (can be combined with --scale)", "W:H");
+    opts.optopt("t", "target", "reduce the image to the given size, choosing the order of vertical and horizontal seam \
+        removals which removes the least energy (can't be combined with -W or -H)", "WIDTHxHEIGHT");
+    opts.optopt("c", "seam-cost", "how to measure the cost of a seam: backward (energy of the removed pixels; the \
+        default) or forward (colour differences added by joining the pixels either side, so -E doesn't \
+        apply)", "COST");
+    opts.optopt("E", "energy-function", "how to calculate the energy of each pixel: dual-gradient (the default), sobel, \
+        scharr, entropy or saliency", "FUNCTION");
+    opts.optopt("b", "border", "how to calculate the energy of pixels on the border: max (the default), mirrored or \
+        wrapped", "POLICY");
+    opts.optopt("", "graph-cut", "find seams as the minimum cut of a graph of the pixels instead of by dynamic \
+        programming, for comparison: connected (like the usual seams) or piecewise (seams can jump between cheap \
+        areas); much slower, and always uses backward energy", "CONNECTIVITY");
+    opts.optopt("j", "threads", "the most threads to use when finding seams (defaults to the number of CPUs)", "COUNT");
+    opts.optopt("", "protect", "a mask image the same size as the input, where white pixels mark parts of the image \
+        which seams should avoid", "MASK-FILE");
+    opts.optopt("", "remove", "a mask image the same size as the input, where white pixels mark an object to remove; \
+        seams are removed until it's gone (before any other reduction)", "MASK-FILE");
+    opts.optopt("f", "format", "the format to save the output image in: png, ppm, pgm or bmp (defaults to the output \
+        file's extension, or the input image's format); the input's format is detected", "FORMAT");
+    opts.optopt("", "frames", "save an animation of the seams being removed, as numbered PNGs in the given directory: each \
+        frame shows the next seam in red before it's removed", "DIRECTORY");
+    opts.optflag("", "pad-frames", "pad animation frames with black to the size of the image before any seams were \
+        removed, so they line up");
+    opts.optopt("", "history", "save every removed seam and the pixels it removed to the given file, so the image can \
+        be rebuilt exactly, or at any width in between, with --replay", "HISTORY-FILE");
+    opts.optopt("", "replay", "treat the input file as a history saved with --history, and output the image as it was \
+        when it was the given width, or width and height (without searching for seams again)", "WIDTH[xHEIGHT]");
+    opts.optflag("", "video", "treat INPUT-FILE as a directory of video frames (in file name order) and save the \
+        carved frames to the directory given by -o, keeping seams in the same place from frame to frame where \
+        possible; only -W and -H can be used to resize");
+    opts.optopt("", "coherence", "with --video, the cost of a seam straying 1 pixel from where it was in the previous \
+        frame, in each row or column (defaults to 10000; 0 carves each frame separately)", "COST");
+    opts.optopt("", "batch", "treat INPUT-FILE as a directory of images, or a wildcard pattern (quoted) such as \
+        'photos/*.png', and resize each image to SIZE (WIDTHxHEIGHT, or a percentage such as 75%), saving them under \
+        the same names in the directory given by -o; images are carved in parallel, by up to -j at once", "SIZE");
+    opts.optflag("v", "verbose", "print out energy and discovered seams");
+    opts.optflag("h", "help", "print this usage information");
+    opts
+}
+
+/// What to do to the image, as given on the command line.
+struct Settings {
+    output_energy: bool,
+    preview_next_seam: bool,
+    width_reduction: usize,
+    height_reduction: usize,
+    width_increase: usize,
+    resize: Option<Geometry>, // --width, --height, --scale and --aspect
+    target: Option<String>,
+    verbose_mode: bool,
+    carver_options: CarverOptions,
+    graph_cut: Option<Connectivity>,
+    protect_mask_path: Option<String>,
+    remove_mask_path: Option<String>,
+    frames_directory: Option<String>,
+    pad_frames: bool,
+    visualisation: Visualisation,
+    energy_dump_path: Option<String>,
+    distances_dump_path: Option<String>,
+    history_path: Option<String>,
+    removal_order_dump_path: Option<String>,
+}
+
+/// Saves the frames of an animation of seams being removed.
+struct Frames {
+    directory: PathBuf,
+    pad_to: Option<(usize, usize)>, // the size to pad every frame to, if any
+    saved: usize,
+}
+
+impl Frames {
+    fn new(directory: &str, pad_to: Option<(usize, usize)>) -> Frames {
+        if let Err(e) = fs::create_dir_all(directory) {
+            println!("Invalid arguments: could not create frame directory {}, because: {}", directory, e);
+            process::exit(1);
+        }
+        Frames { directory: PathBuf::from(directory), pad_to: pad_to, saved: 0 }
+    }
+
+    /// Saves the image with the given seam drawn on it as the next frame. Frames are always 8 bit RGB, so the seam is
+    /// red even in grey images.
+    fn save<P: Pixel>(&mut self, image: &Image<P>, seam: &[usize]) {
+        let mut frame = Image::new(image.pixels.iter().map(|p| p.to_rgb8()).collect(), image.width, image.height);
+        seam_carving::draw_seam(&mut frame, seam);
+        if let Some((width, height)) = self.pad_to {
+            frame = seam_carving::pad(&frame, width, height, RGB { r: 0, g: 0, b: 0 });
+        }
+
+        let path = self.directory.join(format!("frame-{:05}.png", self.saved));
+        let picture = Picture { pixels: Pixels::Rgb(frame.pixels), width: frame.width, height: frame.height };
+        if let Err(e) = picture::save(&path, Format::Png, &picture) {
+            exit_with_error(format!("Failed to save frame to {} because: {}", path.display(), e));
+        }
+        self.saved += 1;
+    }
+}
+
+/// Resizes the image (and draws any energy display or seam preview on it) as the settings say.
+fn carve<P: Pixel>(mut image: Image<P>, settings: &Settings) -> Image<P> {
+    let verbose_mode = settings.verbose_mode;
+    let carver_options = settings.carver_options;
+
+    // the size to resize to is relative to the original image, but seams are only removed to reach it once any marked
+    // pixels have been removed
+    let resize_to = settings.resize.map(|resize| resize.size_for(image.width, image.height));
+    let (width_increase, height_increase) = match resize_to {
+        Some((width, height)) => {
+            println!("Resizing to {} x {}", width, height);
+            (width.saturating_sub(image.width), height.saturating_sub(image.height))
+        },
+        None => (settings.width_increase, 0),
+    };
+    let masked = settings.protect_mask_path.is_some() || settings.remove_mask_path.is_some();
+    if masked && (width_increase > 0 || height_increase > 0) {
+        println!("Invalid arguments: --protect and --remove can only be used when shrinking the image");
+        process::exit(1);
+    }
+
+    if width_increase > 0 {
+        println!("Increasing width of image by {} pixels", width_increase);
+        let pixels = match insertion::insert_vertical_seams(&image.pixels, image.width, image.height,
+                width_increase, carver_options) {
+            Ok(pixels) => pixels,
+            Err(reason) => exit_with_error(reason.to_string()),
+        };
+        image = Image::new(pixels, image.width + width_increase, image.height);
+    }
+    if height_increase > 0 {
+        println!("Increasing height of image by {} pixels", height_increase);
+        // retarget inserts horizontal seams by inserting vertical ones in the transposed image
+        let (width, height) = (image.width, image.height + height_increase);
+        image = match retarget(&image, width, height, carver_options) {
+            Ok(image) => image,
+            Err(reason) => exit_with_error(reason.to_string()),
+        };
+    }
+
+    if verbose_mode { println!("Calculating pixel energies..."); }
+    let mut carver = Carver::with_options(image.pixels.len(), carver_options);
+    if settings.protect_mask_path.is_some() || settings.remove_mask_path.is_some() {
+        let mut mask = vec![Mark::Unmarked; image.pixels.len()];
+        // removal is applied last, so pixels in both masks are removed
+        let masks = [(&settings.protect_mask_path, Mark::Protect), (&settings.remove_mask_path, Mark::Remove)];
+        for &(path, mark) in masks.iter() {
+            if let Some(ref path) = *path {
+                match load_mask(Path::new(path), image.width, image.height) {
+                    Ok(marked) => for (m, &is_marked) in mask.iter_mut().zip(marked.iter()) {
+                        if is_marked {
+                            *m = mark;
+                        }
+                    },
+                    Err(reason) => {
+                        println!("Invalid arguments: could not use {} as a mask, because: {}", path, reason);
+                        process::exit(1);
+                    },
+                }
+            }
+        }
+        carver.set_mask(mask);
+    }
+    carver.calculate_energy(image.width, image.height, &image.pixels);
+
+    if let Some(ref path) = settings.removal_order_dump_path {
+        println!("Finding when each pixel would be removed...");
+        let index = RemovalIndex::build(&image, carver_options);
+        let order = index.order.iter().map(|&order| order as i64).collect::<Vec<_>>();
+        dump("removal order", path, &order, image.width, image.height, settings.visualisation);
+    }
+
+    let mut frames = settings.frames_directory.as_ref().map(|directory|
+        Frames::new(directory, if settings.pad_frames { Some((image.width, image.height)) } else { None }));
+    let mut history = settings.history_path.as_ref().map(|_| SeamHistory::new(&image));
+
+    if settings.remove_mask_path.is_some() {
+        print!("Removing marked pixels");
+        loop {
+            let marked = carver.marked_for_removal(image.width, image.height);
+            if marked.is_empty() {
+                break;
+            }
+            let orientation = seam_carving::removal_orientation(&marked, image.width);
+            if (orientation == Orientation::Vertical && image.width == 1) ||
+                    (orientation == Orientation::Horizontal && image.height == 1) {
+                print!(" image is too small to remove the last {} marked pixels", marked.len());
+                break;
+            }
+            carve_seam(&mut carver, &mut image, orientation, &mut frames, &mut history, settings);
+        }
+        println!("");
+    }
+
+    // --target, and resizing which shrinks both dimensions, choose the cheapest order; otherwise vertical seams go
+    // first
+    let (width_reduction, height_reduction, find_order) = match settings.target {
+        Some(ref target) => match parse_dimensions(target) {
+            Some((w, h)) if w > 0 && h > 0 && w <= image.width && h <= image.height =>
+                (image.width - w, image.height - h, true),
+            _ => {
+                println!("Invalid arguments: --target must be WIDTHxHEIGHT no bigger than the {} x {} image",
+                    image.width, image.height);
+                process::exit(1);
+            },
+        },
+        None => match resize_to {
+            Some((width, height)) if width > image.width || height > image.height => {
+                println!("Invalid arguments: removing the marked pixels left a {} x {} image, which is already \
+                    smaller than {} x {}", image.width, image.height, width, height);
+                process::exit(1);
+            },
+            Some((width, height)) =>
+                (image.width - width, image.height - height, image.width > width && image.height > height),
+            None => (settings.width_reduction, settings.height_reduction, false),
+        },
+    };
+    if width_reduction >= image.width || height_reduction >= image.height {
+        println!("Invalid arguments: can't reduce the {} x {} image by {} x {} (it must be left at least 1 x 1)",
+            image.width, image.height, width_reduction, height_reduction);
+        process::exit(1);
+    }
+    let removal_order = if find_order {
+        println!("Finding optimal order of seam removals to reach {} x {}", image.width - width_reduction,
+            image.height - height_reduction);
+        let order = match ordering::optimal_order(&image.pixels, image.width, image.height, width_reduction,
+                height_reduction, carver_options) {
+            Ok(order) => order,
+            Err(reason) => exit_with_error(reason.to_string()),
+        };
+        if verbose_mode {
+            println!("Optimal order: {:?}", order);
+        }
+        order
+    } else {
+        let mut order = vec![Orientation::Vertical; width_reduction];
+        order.extend(vec![Orientation::Horizontal; height_reduction]);
+        order
+    };
+
+    print!("Removing {} seams", removal_order.len());
+    for &orientation in removal_order.iter() {
+        carve_seam(&mut carver, &mut image, orientation, &mut frames, &mut history, settings);
+    }
     println!("");
+    if let Some(ref mut frames) = frames {
+        frames.save(&image, &[]); // finish with the result
+        println!("Saved {} animation frames", frames.saved);
+    }
+    if let (Some(ref path), Some(mut history)) = (settings.history_path.as_ref(), history) {
+        history.result = image.clone();
+        if let Err(e) = File::create(path).and_then(|mut file| file.write_all(&history.to_bytes())) {
+            exit_with_error(format!("Failed to save seam history to {} because: {}", path, e));
+        }
+        println!("Saved history of {} seam removals to {}", history.seams.len(), path);
+    }
+
+    if let Some(ref path) = settings.energy_dump_path {
+        let energy = carver.energy.iter().map(|&energy| energy as i64).collect::<Vec<_>>();
+        dump("energy", path, &energy, image.width, image.height, settings.visualisation);
+    }
+
+    // the preview and distances are of the kind of seam that was last removed, defaulting to vertical
+    let next_seam = if settings.preview_next_seam || settings.distances_dump_path.is_some() {
+        let orientation = removal_order.last().cloned().unwrap_or(Orientation::Vertical);
+        if verbose_mode { println!("Finding next {:?} seam...", orientation); }
+        Some(carver.find_oriented_seam(image.width, image.height, orientation))
+    } else {
+        None
+    };
+
+    if let Some(ref path) = settings.distances_dump_path {
+        let distances = carver.distances().expect("a seam has just been searched for");
+        dump("distances", path, distances, image.width, image.height, settings.visualisation);
+    }
 
-    if output_energy {
+    if settings.output_energy {
         println!("Converting image to display its energies");
-        for (pixel, energy) in subset_by_width_and_height(bitmap.buffer.as_mut(), bitmap.width, bitmap.height)
-                .iter_mut().zip(carver.energy.iter()) {
-            let relative_energy = (energy / carving::MAX_PIXEL_ENERGY * 255) as u8;
-            pixel.r = relative_energy;
-            pixel.g = relative_energy;
-            pixel.b = relative_energy;
-        }
+        image = seam_carving::energy_image(&carver, image.width, image.height, settings.visualisation);
     }
 
-    if preview_next_seam {
+    if let (true, Some(seam)) = (settings.preview_next_seam, next_seam) {
         if verbose_mode {
-            println!("Seam found for preview: {:?}", seam);
-            println!("As energy for preview:  {:?}", seam.iter().map(|seam_pixel_index|
-                carver.energy[*seam_pixel_index]).collect::<Vec<_>>());
+            print_seam("Seam found for preview:", &seam, &carver);
         }
 
         println!("Updating image with preview (in red) of next seam that would be removed");
-        let image_pixels = subset_by_width_and_height(bitmap.buffer.as_mut(), bitmap.width, bitmap.height);
-        for pixel_index in seam {
-            image_pixels[pixel_index].r = 255;
-            image_pixels[pixel_index].g = 0;
-            image_pixels[pixel_index].b = 0;
+        seam_carving::draw_seam(&mut image, &seam);
+    }
+
+    image
+}
+
+/// Finds and removes the lowest energy seam of the given orientation from both the image and the carver, which updates
+/// its energy to match. If an animation is being saved, the seam is shown in a frame first, and if a history is being
+/// kept, the seam is recorded in it.
+fn carve_seam<P: Pixel>(carver: &mut Carver, image: &mut Image<P>, orientation: Orientation, frames: &mut Option<Frames>,
+        history: &mut Option<SeamHistory<P>>, settings: &Settings) {
+    let verbose_mode = settings.verbose_mode;
+    let seam = match settings.graph_cut {
+        Some(connectivity) => graph_cut::find_seam(carver, image.width, image.height, orientation, connectivity),
+        None => carver.find_oriented_seam(image.width, image.height, orientation),
+    };
+    if let Some(ref mut frames) = *frames {
+        frames.save(image, &seam);
+    }
+    if let Some(ref mut history) = *history {
+        history.record(image, orientation, &seam);
+    }
+    if verbose_mode {
+        println!("");
+        print_seam(&format!("Will remove {:?} seam:", orientation), &seam, carver);
+        if settings.graph_cut.is_some() {
+            let dynamic = carver.find_oriented_seam(image.width, image.height, orientation);
+            print_seam("Dynamic programming would remove:", &dynamic, carver);
         }
+        println!("Updating pixel energies...");
     }
 
-    match matches.opt_str("o") {
-        Some(output_img_str) => {
-            let output_img_path = &Path::new(&output_img_str);
+    seam_carving::remove_seam(carver, image, orientation, &seam);
 
-            // image could be smaller now, so make sure we don't try to save more pixels than we have space for
-            let portion_to_save = subset_by_width_and_height(bitmap.buffer.as_mut(), bitmap.width, bitmap.height);
+    if !verbose_mode {
+        use std::io;
+        print!(".");
+        io::stdout().flush().unwrap();
+    }
+}
+
+/// Shrinks each frame in the input directory by the width and height reductions in the settings, with seams which
+/// stay put from frame to frame as far as `coherence` allows, saving them under the same names in the output directory.
+fn carve_video(input: &Path, output: &Path, settings: &Settings, coherence: i32, output_format: Option<Format>) {
+    let mut paths = match fs::read_dir(input) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok()).map(|entry| entry.path())
+            .filter(|path| path.is_file()).collect::<Vec<_>>(),
+        Err(e) => {
+            println!("Invalid arguments: could not read frame directory {}, because: {}", input.display(), e);
+            process::exit(1);
+        },
+    };
+    paths.sort();
+    let mut pictures = Vec::with_capacity(paths.len());
+    let mut formats = Vec::with_capacity(paths.len());
+    for path in &paths {
+        let (picture, format) = match picture::load(path) {
+            Ok(loaded) => loaded,
+            Err(reason) => exit_with_error(format!("Could not load {}, because: {}", path.display(), reason)),
+        };
+        pictures.push(picture);
+        formats.push(format);
+    }
+    let (width, height) = match pictures.first() {
+        Some(first) => (first.width, first.height),
+        None => {
+            println!("Invalid arguments: {} has no frames in it", input.display());
+            process::exit(1);
+        },
+    };
+    if pictures.iter().any(|picture| picture.width != width || picture.height != height) {
+        println!("Invalid arguments: every frame must be the same size");
+        process::exit(1);
+    }
+    if settings.width_reduction >= width || settings.height_reduction >= height {
+        println!("Invalid arguments: can't reduce {} x {} frames by {} x {}", width, height,
+            settings.width_reduction, settings.height_reduction);
+        process::exit(1);
+    }
+    println!("Carving {} frames of {} x {} video", pictures.len(), width, height);
 
-            if let Err(e) = lodepng::encode24_file(output_img_path, portion_to_save, bitmap.width, bitmap.height) {
-                panic!("Failed to save png to {} because: {}", output_img_str, e);
+    let (new_width, new_height) = (width - settings.width_reduction, height - settings.height_reduction);
+    macro_rules! carve_each_format {
+        ($($format:ident),*) => {
+            match pictures[0].pixels {
+                $(Pixels::$format(_) => {
+                    let frames = pictures.into_iter().map(|picture| match picture.pixels {
+                        Pixels::$format(pixels) => Image::new(pixels, width, height),
+                        _ => {
+                            println!("Invalid arguments: every frame must have the same colour type and bit depth");
+                            process::exit(1);
+                        },
+                    }).collect::<Vec<_>>();
+                    let carved = match video::retarget_frames(&frames, new_width, new_height,
+                            settings.carver_options, coherence) {
+                        Ok(carved) => carved,
+                        Err(reason) => exit_with_error(reason.to_string()),
+                    };
+                    carved.into_iter().map(|image|
+                            Picture { pixels: Pixels::$format(image.pixels), width: image.width, height: image.height }
+                        ).collect::<Vec<_>>()
+                },)*
             }
+        }
+    }
+    let carved = carve_each_format!(Grey, Grey16, GreyAlpha, GreyAlpha16, Rgb, Rgb16, Rgba, Rgba16);
 
-            println!("Saved output image to {}", output_img_str);
+    if let Err(e) = fs::create_dir_all(output) {
+        exit_with_error(format!("Failed to create {} because: {}", output.display(), e));
+    }
+    for ((path, format), picture) in paths.iter().zip(formats.into_iter()).zip(carved.iter()) {
+        let output_path = output.join(path.file_name().expect("frames are files"));
+        let output_format = output_format.unwrap_or(format);
+        if let Err(e) = picture::save(&output_path, output_format, picture) {
+            exit_with_error(format!("Failed to save {:?} image to {} because: {}", output_format,
+                output_path.display(), e));
+        }
+    }
+    println!("Saved {} frames to {}", carved.len(), output.display());
+}
+
+/// Resizes every image `input` names (see `batch::find_inputs`) into the output directory, then prints how long each
+/// took and which failed. Exits with an error if any did.
+fn carve_batch(input: &str, output: &Path, size: batch::Size, settings: &Settings, output_format: Option<Format>) {
+    let inputs = match batch::find_inputs(input) {
+        Ok(ref inputs) if inputs.is_empty() => {
+            println!("Invalid arguments: {} doesn't match any files", input);
+            process::exit(1);
+        },
+        Ok(inputs) => inputs,
+        Err(reason) => {
+            println!("Invalid arguments: {}", reason);
+            process::exit(1);
         },
-        None => println!("Not saving output image; specify -o flag if you want to save the result"),
     };
+    if batch::writes_over_inputs(&inputs, output) {
+        println!("Invalid arguments: -o can't be the directory the images are in, since they'd be overwritten");
+        process::exit(1);
+    }
+    if let Err(e) = fs::create_dir_all(output) {
+        exit_with_error(format!("Failed to create {} because: {}", output.display(), e));
+    }
+    let workers = settings.carver_options.threads;
+    println!("Carving {} images, {} at a time", inputs.len(), cmp::min(workers, inputs.len()));
+
+    let start = Instant::now();
+    let outcomes = batch::carve_files(&inputs, output, size, output_format, settings.carver_options, workers);
+    let elapsed = start.elapsed();
+
+    let mut failures = 0;
+    let mut carving_time = 0.0;
+    for outcome in &outcomes {
+        let seconds = seconds(outcome.duration);
+        carving_time += seconds;
+        match outcome.result {
+            Ok(((width, height), (new_width, new_height))) => println!("{:>8.3}s  {} ({} x {}) -> {} ({} x {})",
+                seconds, outcome.input.display(), width, height, outcome.output.display(), new_width, new_height),
+            Err(ref reason) => {
+                failures += 1;
+                println!("{:>8.3}s  {} failed, because: {}", seconds, outcome.input.display(), reason);
+            },
+        }
+    }
+    println!("Carved {} of {} images in {:.3}s ({:.3}s per image on average)", outcomes.len() - failures,
+        outcomes.len(), seconds(elapsed), carving_time / outcomes.len() as f64);
+    if failures > 0 {
+        exit_with_error(format!("{} of {} images failed", failures, outcomes.len()));
+    }
 }
 
-fn subset_by_width_and_height<A>(slice: &mut [A], width: usize, height: usize) -> &mut [A] {
-    &mut slice[..(width * height)]
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1e9
 }
 
-/// For each index `A` of `to_remove` into `slice`, set `slice[A] = slice[A + 1]`. The last `to_remove.len()` items in
-/// `slice` will contain junk after this. Runs in linear time and requires `to_remove` to be sorted w.r.t. `slice`.
-fn lazy_remove_indexes_of<A: Clone>(slice: &mut [A], to_remove: &Vec<usize>) {
-    for (offset, start) in to_remove.iter().enumerate() {
-        let finish = if offset < to_remove.len() - 1 {
-            to_remove[offset + 1]
-        } else {
-            slice.len()
-        };
-        for idx in (start + 1)..finish {
-            let src = idx;
-            let dst = idx - offset - 1;
-            // move element at src to dst; we don't care about what happens to the dst element, which we're deleting
-            slice.swap(src, dst);
+/// Rebuilds the image in a seam history file as it was when it was `width` pixels wide (and `height` pixels high, if
+/// given; otherwise as it first was at that width).
+fn replay(path: &Path, width: usize, height: Option<usize>) -> Picture {
+    let mut data = Vec::new();
+    if let Err(e) = File::open(path).and_then(|mut file| file.read_to_end(&mut data)) {
+        exit_with_error(format!("Could not load {}, because: {}", path.display(), e));
+    }
+    macro_rules! replay_each_format {
+        ($(($format:ident, $pixel:ty)),*) => {
+            match history::format_name_of(&data) {
+                $(Some(name) if name == <$pixel as Pixel>::format_name() => {
+                    let history = match SeamHistory::<$pixel>::from_bytes(&data) {
+                        Ok(history) => history,
+                        Err(reason) =>
+                            exit_with_error(format!("Could not load {}, because: {}", path.display(), reason)),
+                    };
+                    let removed = match height {
+                        Some(height) => history.removals_to_size(width, height),
+                        None => history.removals_to_width(width),
+                    };
+                    let removed = match removed {
+                        Some(removed) => removed,
+                        None => {
+                            let original = history.image_after(0);
+                            println!("Invalid arguments: the history never reaches {}{} (it goes from {} x {} to \
+                                {} x {})", width, height.map_or(String::new(), |height| format!(" x {}", height)),
+                                original.width, original.height, history.result.width, history.result.height);
+                            process::exit(1);
+                        },
+                    };
+                    let image = history.image_after(removed);
+                    Picture { pixels: Pixels::$format(image.pixels), width: image.width, height: image.height }
+                },)*
+                _ => {
+                    println!("Invalid arguments: {} is not a seam history saved with --history", path.display());
+                    process::exit(1);
+                },
+            }
         }
     }
+    replay_each_format!((Grey, Grey<u8>), (Grey16, Grey<u16>), (GreyAlpha, GreyAlpha<u8>),
+        (GreyAlpha16, GreyAlpha<u16>), (Rgb, RGB<u8>), (Rgb16, RGB<u16>), (Rgba, RGBA<u8>), (Rgba16, RGBA<u16>))
+}
+
+/// The size given by --width, --height, --scale and --aspect, if any of them were; exits if they're invalid.
+fn parse_geometry(matches: &Matches) -> Option<Geometry> {
+    if !["width", "height", "scale", "aspect"].iter().any(|&name| matches.opt_present(name)) {
+        return None;
+    }
+    let dimension = |name: &str| matches.opt_str(name).map(|value| match value.parse() {
+        Ok(length) if length > 0 => length,
+        _ => {
+            println!("Invalid arguments: --{} must be a number of pixels, not {}", name, value);
+            process::exit(1);
+        },
+    });
+    let scale = matches.opt_str("scale").map(|value| geometry::parse_scale(&value).unwrap_or_else(|| {
+        println!("Invalid arguments: --scale must be a percentage such as 75%, not {}", value);
+        process::exit(1);
+    }));
+    let aspect = matches.opt_str("aspect").map(|value| geometry::parse_aspect(&value).unwrap_or_else(|| {
+        println!("Invalid arguments: --aspect must be a ratio such as 16:9, not {}", value);
+        process::exit(1);
+    }));
+    let resize = Geometry { width: dimension("width"), height: dimension("height"), scale: scale, aspect: aspect };
+    if resize.scale.is_some() && (resize.width.is_some() || resize.height.is_some()) {
+        println!("Invalid arguments: --scale can't be combined with --width or --height");
+        process::exit(1);
+    }
+    if resize.aspect.is_some() && resize.width.is_some() && resize.height.is_some() {
+        println!("Invalid arguments: --aspect can't be combined with both --width and --height");
+        process::exit(1);
+    }
+    Some(resize)
+}
+
+/// Reports a file which couldn't be read or written (or other failure which isn't the arguments' fault) and exits.
+/// Invalid arguments exit with 1, and these with 2.
+fn exit_with_error(message: String) -> ! {
+    println!("Error: {}", message);
+    process::exit(2);
+}
+
+/// The value of the given numeric option, or `default` if it wasn't given; exits if it isn't a number.
+fn number_option<T: FromStr>(matches: &Matches, name: &str, default: T) -> T {
+    match matches.opt_str(name) {
+        None => default,
+        Some(value) => match value.parse() {
+            Ok(number) => number,
+            Err(_) => {
+                println!("Invalid arguments: --{} must be a number, not {}", name, value);
+                process::exit(1);
+            },
+        },
+    }
+}
+
+/// Loads a mask image, returning whether each of its pixels is marked (i.e. closer to white than black).
+fn load_mask(path: &Path, width: usize, height: usize) -> Result<Vec<bool>, String> {
+    let (mask, _) = try!(picture::load(path).map_err(|e| e.to_string()));
+    if mask.width != width || mask.height != height {
+        return Err(format!("it is {} x {} but the image is {} x {}", mask.width, mask.height, width, height));
+    }
+    Ok(mask.to_rgb8().iter().map(|p| p.r as u32 + p.g as u32 + p.b as u32 > 255 * 3 / 2).collect())
+}
+
+/// Saves a value for each pixel of a `width` x `height` image, as CSV if the path ends in .csv, otherwise as an image.
+fn dump(name: &str, path: &str, values: &[i64], width: usize, height: usize, visualisation: Visualisation) {
+    let path = Path::new(path);
+    let result = if path.extension().map_or(false, |extension| extension == "csv") {
+        File::create(path).and_then(|mut file| visualisation::write_csv(&mut file, values, width))
+            .map_err(|e| Error::Io(e.to_string()))
+    } else {
+        let picture = Picture { pixels: Pixels::Rgb(visualisation.colours(values)), width: width, height: height };
+        picture::save(path, Format::from_path(path).unwrap_or(Format::Png), &picture)
+    };
+    if let Err(e) = result {
+        exit_with_error(format!("Failed to save {} to {} because: {}", name, path.display(), e));
+    }
+    println!("Saved {} to {}", name, path.display());
+}
+
+fn print_seam(label: &str, seam: &Vec<usize>, carver: &Carver) {
+    println!("{:<32}{:?}", label, seam);
+    println!("{:<32}{:?}", "As energy:", seam.iter().map(|seam_pixel_index|
+        carver.energy[*seam_pixel_index]).collect::<Vec<_>>());
+    println!("{:<32}{}", "Total energy:", seam.iter().map(|&pixel| carver.energy[pixel] as i64).sum::<i64>());
+}
+
+/// Parses dimensions given as WIDTHxHEIGHT, e.g. 640x480.
+fn parse_dimensions(s: &str) -> Option<(usize, usize)> {
+    let mut parts = s.splitn(2, 'x');
+    match (parts.next().and_then(|w| w.parse().ok()), parts.next().and_then(|h| h.parse().ok())) {
+        (Some(w), Some(h)) => Some((w, h)),
+        _ => None,
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::lazy_remove_indexes_of;
+    use getopts::Matches;
+    use lodepng::{Grey, RGB};
+    use seam_carving::carving::CarverOptions;
+    use seam_carving::geometry::Geometry;
+    use seam_carving::picture::{self, Format, Picture, Pixels};
+    use seam_carving::visualisation::{ColourMap, Scaling, Visualisation};
+    use seam_carving::Image;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+    use super::{carve, options, parse_dimensions, parse_geometry, Settings};
+
+    /// A temporary file, unique to this process, which is removed when it's dropped.
+    struct TempFile(PathBuf);
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn parse(args: &[&str]) -> Matches {
+        let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        options().parse(&args).unwrap()
+    }
+
+    /// Settings which only resize the image to the given geometry.
+    fn resizing_to(resize: Geometry) -> Settings {
+        Settings {
+            output_energy: false,
+            preview_next_seam: false,
+            width_reduction: 0,
+            height_reduction: 0,
+            width_increase: 0,
+            resize: Some(resize),
+            target: None,
+            verbose_mode: false,
+            carver_options: CarverOptions::default(),
+            graph_cut: None,
+            protect_mask_path: None,
+            remove_mask_path: None,
+            frames_directory: None,
+            pad_frames: false,
+            visualisation: Visualisation { scaling: Scaling::Linear, colour_map: ColourMap::Grey },
+            energy_dump_path: None,
+            distances_dump_path: None,
+            history_path: None,
+            removal_order_dump_path: None,
+        }
+    }
+
+    /// A flat image; these tests only check that images are resized to the right size.
+    fn image(width: usize, height: usize) -> Image<RGB<u8>> {
+        Image::new(vec![RGB { r: 50, g: 100, b: 150 }; width * height], width, height)
+    }
+
+    #[test]
+    fn parses_geometry() {
+        assert_eq!(parse_geometry(&parse(&["in.png", "-W", "10"])), None);
+        assert_eq!(parse_geometry(&parse(&["in.png", "--width", "800"])),
+            Some(Geometry { width: Some(800), ..Geometry::default() }));
+        assert_eq!(parse_geometry(&parse(&["in.png", "--height", "600", "--aspect", "4:3"])),
+            Some(Geometry { height: Some(600), aspect: Some((4.0, 3.0)), ..Geometry::default() }));
+        assert_eq!(parse_geometry(&parse(&["in.png", "--scale", "75%", "--aspect", "16:9"])),
+            Some(Geometry { scale: Some(0.75), aspect: Some((16.0, 9.0)), ..Geometry::default() }));
+    }
+
+    #[test]
+    fn resizes_by_inserting_rows() {
+        let taller = carve(image(5, 4), &resizing_to(Geometry { height: Some(7), ..Geometry::default() }));
+        assert_eq!((taller.width, taller.height, taller.pixels.len()), (5, 7, 5 * 7));
+
+        let bigger = carve(image(5, 4), &resizing_to(Geometry { scale: Some(1.5), ..Geometry::default() }));
+        assert_eq!((bigger.width, bigger.height, bigger.pixels.len()), (8, 6, 8 * 6));
+    }
 
     #[test]
-    fn lazy_remove_indexes_of_works_correctly() {
-        let mut vec = (0..11).collect::<Vec<u32>>();
-        let to_remove = vec!(1, 3, 7);
+    fn resizes_by_removing_seams_in_both_directions() {
+        let resize = Geometry { width: Some(5), height: Some(4), ..Geometry::default() };
 
-        lazy_remove_indexes_of(&mut vec[..], &to_remove);
-        let vec_len = vec.len();
-        vec.truncate(vec_len - to_remove.len()); // only the first 7 elements of vec are valid now
+        let smaller = carve(image(8, 6), &resizing_to(resize));
 
-        // 0 1 2 3 4 5 6 7 8 9 10 11 gets turned into
-        // 0   2   4 5 6   8 9 10 11
-        assert_eq!(vec, vec!(0, 2, 4, 5, 6, 8, 9, 10));
+        assert_eq!((smaller.width, smaller.height, smaller.pixels.len()), (5, 4, 5 * 4));
     }
 
     #[test]
-    fn lazy_remove_indexes_of_works_correctly_on_edges() {
-        let mut vec = (0..5).collect::<Vec<u32>>();
-        let to_remove = vec!(0, 5);
+    fn resizes_to_the_requested_size_after_removing_marked_pixels() {
+        // marks a column of the 8 x 6 image, which takes a seam to remove
+        let mask = TempFile(env::temp_dir().join(format!("seam-carving-remove-mask-{}.pgm", process::id())));
+        let pixels = (0..(8 * 6)).map(|i| Grey(if i % 8 == 2 { 255 } else { 0 })).collect();
+        picture::save(&mask.0, Format::Pnm, &Picture { pixels: Pixels::Grey(pixels), width: 8, height: 6 }).unwrap();
 
-        lazy_remove_indexes_of(&mut vec[..], &to_remove);
-        let vec_len = vec.len();
-        vec.truncate(vec_len - to_remove.len()); // only the first 3 elements of vec are valid now
+        for &(width, height) in [(Some(5), None), (Some(5), Some(4))].iter() {
+            let mut settings = resizing_to(Geometry { width: width, height: height, ..Geometry::default() });
+            settings.remove_mask_path = Some(mask.0.to_str().unwrap().to_string());
 
-        // 0 1 2 3 4 gets turned into
-        //   1 2 3
-        assert_eq!(vec, vec!(1, 2, 3));
+            let carved = carve(image(8, 6), &settings);
+
+            assert_eq!((carved.width, carved.height), (5, height.unwrap_or(6)));
+        }
+    }
+
+    #[test]
+    fn parses_dimensions() {
+        assert_eq!(parse_dimensions("640x480"), Some((640, 480)));
+        assert_eq!(parse_dimensions("640"), None);
+        assert_eq!(parse_dimensions("640xabc"), None);
     }
 }
@@ -0,0 +1,187 @@
+use carving::{Carver, CarverOptions, Orientation};
+use pixel::Pixel;
+use {remove_seam, Error, Image};
+
+/// Removes the lowest cost seam of the given orientation, returning the cost of the removed seam.
+fn remove_cheapest_seam<P: Pixel>(image: &mut Image<P>, carver: &mut Carver, orientation: Orientation) -> u64 {
+    carver.calculate_energy(image.width, image.height, &image.pixels);
+    let seam = carver.find_oriented_seam(image.width, image.height, orientation);
+    let seam_cost = carver.last_seam_cost() as u64;
+    remove_seam(carver, image, orientation, &seam);
+    seam_cost
+}
+
+/// The total cost of the seams removed from the image in the given order.
+fn cost_of_order<P: Pixel>(mut image: Image<P>, carver: &mut Carver, order: &[Orientation]) -> u64 {
+    order.iter().map(|&orientation| remove_cheapest_seam(&mut image, carver, orientation)).sum()
+}
+
+/// Finds the order in which to remove `width_reduction` vertical seams and `height_reduction` horizontal seams so that the
+/// total cost (energy, when using `SeamCost::Backward`) of all removed seams is minimised, using the transport map dynamic
+/// programme from "Seam Carving for Content-Aware Image Resizing" (Avidan & Shamir, 2007). As in the paper, each cell
+/// only keeps the image from its cheapest path, so this is optimal with respect to the map rather than over every possible
+/// order:
+///
+/// ```text
+/// T(r, c) = min(T(r - 1, c) + E(horizontal seam of image at (r - 1, c)),
+///               T(r, c - 1) + E(vertical seam of image at (r, c - 1)))
+/// ```
+///
+/// where `r` and `c` are the number of horizontal and vertical seams removed so far. Each cell needs the image it
+/// produces, so only the previous row of images is kept; that's still up to `width_reduction + 1` copies of the image, and
+/// `2 * (width_reduction + 1) * (height_reduction + 1)` seams have to be found.
+///
+/// Keeping one image per cell can lead the map astray (particularly with `SeamCost::Forward`, where a seam's cost
+/// depends on the pixels either side of it), so the result is also compared with removing all the vertical seams first
+/// and with removing all the horizontal seams first, and the cheapest of the three is returned.
+///
+/// The image must be left at least 1 x 1.
+pub fn optimal_order<P: Pixel>(pixels: &[P], width: usize, height: usize, width_reduction: usize, height_reduction: usize,
+        options: CarverOptions) -> Result<Vec<Orientation>, Error> {
+    if width_reduction >= width || height_reduction >= height {
+        return Err(Error::InvalidSize { width: width, height: height, new_width: width.saturating_sub(width_reduction),
+            new_height: height.saturating_sub(height_reduction) });
+    }
+    let mut carver = Carver::with_options(width * height, options);
+    let cols = width_reduction + 1;
+
+    // choices[r * cols + c] = the orientation of the last seam removed on the cheapest path to (r, c)
+    let mut choices = vec![None; (height_reduction + 1) * cols];
+    let mut prev_row: Vec<(u64, Image<P>)> = Vec::with_capacity(cols);
+
+    for r in 0..(height_reduction + 1) {
+        let mut curr_row: Vec<(u64, Image<P>)> = Vec::with_capacity(cols);
+        for c in 0..cols {
+            let from_above = if r > 0 {
+                let (cost, ref image) = prev_row[c];
+                let mut image = image.clone();
+                let seam_cost = remove_cheapest_seam(&mut image, &mut carver, Orientation::Horizontal);
+                Some((cost + seam_cost, image))
+            } else {
+                None
+            };
+            let from_left = if c > 0 {
+                let (cost, ref image) = curr_row[c - 1];
+                let mut image = image.clone();
+                let seam_cost = remove_cheapest_seam(&mut image, &mut carver, Orientation::Vertical);
+                Some((cost + seam_cost, image))
+            } else {
+                None
+            };
+
+            let (choice, cell) = match (from_above, from_left) {
+                (None, None) => {
+                    let original = Image::new(pixels[..(width * height)].to_vec(), width, height);
+                    (None, (0, original))
+                },
+                (Some(above), None) => (Some(Orientation::Horizontal), above),
+                (None, Some(left)) => (Some(Orientation::Vertical), left),
+                (Some(above), Some(left)) => if above.0 <= left.0 {
+                    (Some(Orientation::Horizontal), above)
+                } else {
+                    (Some(Orientation::Vertical), left)
+                },
+            };
+            choices[r * cols + c] = choice;
+            curr_row.push(cell);
+        }
+        prev_row = curr_row;
+    }
+
+    let cost = prev_row[width_reduction].0;
+
+    // walk back from the target size to the original image to recover the order
+    let mut order = Vec::with_capacity(width_reduction + height_reduction);
+    let (mut r, mut c) = (height_reduction, width_reduction);
+    while let Some(orientation) = choices[r * cols + c] {
+        order.push(orientation);
+        match orientation {
+            Orientation::Horizontal => r -= 1,
+            Orientation::Vertical => c -= 1,
+        }
+    }
+    order.reverse();
+
+    let mut vertical_first = vec![Orientation::Vertical; width_reduction];
+    vertical_first.extend(vec![Orientation::Horizontal; height_reduction]);
+    let mut horizontal_first = vec![Orientation::Horizontal; height_reduction];
+    horizontal_first.extend(vec![Orientation::Vertical; width_reduction]);
+    let (cheapest, _) = [vertical_first, horizontal_first].iter().fold((order, cost), |(best, best_cost), naive| {
+        let original = Image::new(pixels[..(width * height)].to_vec(), width, height);
+        let naive_cost = cost_of_order(original, &mut carver, naive);
+        if naive_cost < best_cost { (naive.clone(), naive_cost) } else { (best, best_cost) }
+    });
+    Ok(cheapest)
+}
+
+#[cfg(test)]
+mod tests {
+    use carving::{Carver, CarverOptions, Orientation, SeamCost};
+    use lodepng::RGB;
+    use super::{cost_of_order, optimal_order};
+    use Image;
+
+    /// A 7 x 6 image with a bright vertical stripe and some noise, so that the order of seam removals matters.
+    fn sample_image() -> Vec<RGB<u8>> {
+        (0..(7 * 6)).map(|i| {
+            let (x, y) = (i % 7, i / 7);
+            let v = if x == 3 { 255 } else { ((x * 37 + y * 91) % 113) as u8 };
+            RGB { r: v, g: v / 2, b: 255 - v }
+        }).collect()
+    }
+
+    /// The total cost of the seams removed from the sample image in the given order.
+    fn cost_of(order: &[Orientation], options: CarverOptions) -> u64 {
+        cost_of_order(Image::new(sample_image(), 7, 6), &mut Carver::with_options(7 * 6, options), order)
+    }
+
+    #[test]
+    fn costs_no_more_than_removing_all_of_one_orientation_first() {
+        let forward = CarverOptions { seam_cost: SeamCost::Forward, ..CarverOptions::default() };
+        for &options in [CarverOptions::default(), forward].iter() {
+            for &(width_reduction, height_reduction) in [(3, 2), (1, 4), (5, 1), (4, 4)].iter() {
+                let mut vertical_first = vec![Orientation::Vertical; width_reduction];
+                vertical_first.extend(vec![Orientation::Horizontal; height_reduction]);
+                let mut horizontal_first = vec![Orientation::Horizontal; height_reduction];
+                horizontal_first.extend(vec![Orientation::Vertical; width_reduction]);
+
+                let order = optimal_order(&sample_image(), 7, 6, width_reduction, height_reduction, options).unwrap();
+
+                let cost = cost_of(&order, options);
+                assert!(cost <= cost_of(&vertical_first, options), "{:?} costs more than {:?}", order,
+                    vertical_first);
+                assert!(cost <= cost_of(&horizontal_first, options), "{:?} costs more than {:?}", order,
+                    horizontal_first);
+            }
+        }
+    }
+
+    #[test]
+    fn removes_requested_number_of_seams_in_each_direction() {
+        let order = optimal_order(&sample_image(), 7, 6, 3, 2, CarverOptions::default()).unwrap();
+
+        assert_eq!(order.iter().filter(|&&o| o == Orientation::Vertical).count(), 3);
+        assert_eq!(order.iter().filter(|&&o| o == Orientation::Horizontal).count(), 2);
+    }
+
+    #[test]
+    fn only_removes_vertical_seams_when_only_width_is_reduced() {
+        let options = CarverOptions { seam_cost: SeamCost::Forward, ..CarverOptions::default() };
+
+        let order = optimal_order(&sample_image(), 7, 6, 2, 0, options).unwrap();
+
+        assert_eq!(order, vec![Orientation::Vertical, Orientation::Vertical]);
+    }
+
+    #[test]
+    fn needs_no_seams_for_no_reduction() {
+        assert_eq!(optimal_order(&sample_image(), 7, 6, 0, 0, CarverOptions::default()), Ok(vec![]));
+    }
+
+    #[test]
+    fn refuses_to_remove_every_seam() {
+        assert!(optimal_order(&sample_image(), 7, 6, 7, 0, CarverOptions::default()).is_err());
+        assert!(optimal_order(&sample_image(), 7, 6, 0, 6, CarverOptions::default()).is_err());
+        assert!(optimal_order(&sample_image()[..0], 0, 0, 0, 0, CarverOptions::default()).is_err());
+    }
+}
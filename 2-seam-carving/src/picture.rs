@@ -0,0 +1,189 @@
+use bmp;
+use error::Error;
+use lodepng::{self, Bitmap, ColorType, Grey, GreyAlpha, RGB, RGBA};
+use pixel::Pixel;
+use pnm;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// The file formats images can be read from and written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Png,
+    Pnm, // PGM for grey images, PPM for colour ones
+    Bmp,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "png" => Some(Format::Png),
+            "pnm" | "pgm" | "ppm" => Some(Format::Pnm),
+            "bmp" => Some(Format::Bmp),
+            _ => None,
+        }
+    }
+
+    /// The format named by the path's extension, if it's one we know.
+    pub fn from_path(path: &Path) -> Option<Format> {
+        path.extension().and_then(|extension| extension.to_str())
+            .and_then(|extension| Format::from_name(&extension.to_lowercase()))
+    }
+
+    /// The format of an image's data, going by its first few bytes.
+    pub fn of_data(data: &[u8]) -> Option<Format> {
+        if data.starts_with(b"\x89PNG") {
+            Some(Format::Png)
+        } else if data.starts_with(b"BM") {
+            Some(Format::Bmp)
+        } else if data.len() >= 2 && data[0] == b'P' && b"2356".contains(&data[1]) {
+            Some(Format::Pnm)
+        } else {
+            None
+        }
+    }
+}
+
+/// The pixels of a decoded image, in the format they were stored in (except that palette images are expanded to 8 bit
+/// RGBA and grey images with fewer than 8 bits per pixel to 8 bit grey).
+pub enum Pixels {
+    Grey(Vec<Grey<u8>>),
+    Grey16(Vec<Grey<u16>>),
+    GreyAlpha(Vec<GreyAlpha<u8>>),
+    GreyAlpha16(Vec<GreyAlpha<u16>>),
+    Rgb(Vec<RGB<u8>>),
+    Rgb16(Vec<RGB<u16>>),
+    Rgba(Vec<RGBA<u8>>),
+    Rgba16(Vec<RGBA<u16>>),
+}
+
+pub struct Picture {
+    pub pixels: Pixels,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Picture {
+    /// The picture's pixels as 8 bit RGB (see `Pixel::to_rgb8`).
+    pub fn to_rgb8(&self) -> Vec<RGB<u8>> {
+        match self.pixels {
+            Pixels::Grey(ref pixels) => rgb8(pixels),
+            Pixels::Grey16(ref pixels) => rgb8(pixels),
+            Pixels::GreyAlpha(ref pixels) => rgb8(pixels),
+            Pixels::GreyAlpha16(ref pixels) => rgb8(pixels),
+            Pixels::Rgb(ref pixels) => rgb8(pixels),
+            Pixels::Rgb16(ref pixels) => rgb8(pixels),
+            Pixels::Rgba(ref pixels) => rgb8(pixels),
+            Pixels::Rgba16(ref pixels) => rgb8(pixels),
+        }
+    }
+}
+
+/// Loads an image (in whichever format its contents or extension say it's in), keeping its colour type and bit depth
+/// where the format has them so it can be saved the same way. Returns the image along with its format.
+pub fn load(path: &Path) -> Result<(Picture, Format), Error> {
+    let mut data = Vec::new();
+    if let Err(e) = File::open(path).and_then(|mut file| file.read_to_end(&mut data)) {
+        return Err(Error::Io(e.to_string()));
+    }
+    let format = Format::of_data(&data).or_else(|| Format::from_path(path)).unwrap_or(Format::Png);
+    let picture = match format {
+        Format::Png => decode_png(&data),
+        Format::Pnm => pnm::decode(&data),
+        Format::Bmp => bmp::decode(&data),
+    };
+    picture.map(|picture| (picture, format)).map_err(Error::Format)
+}
+
+/// Saves the picture in the given format, as close to the colour type and bit depth of its pixels as the format allows.
+pub fn save(path: &Path, format: Format, picture: &Picture) -> Result<(), Error> {
+    let data = match format {
+        Format::Png => return save_png(path, picture).map_err(Error::Io), // lodepng writes the file itself
+        Format::Pnm => pnm::encode(picture),
+        Format::Bmp => bmp::encode(picture),
+    };
+    File::create(path).and_then(|mut file| file.write_all(&data)).map_err(|e| Error::Io(e.to_string()))
+}
+
+fn decode_png(data: &[u8]) -> Result<Picture, String> {
+    let mut state = lodepng::State::new();
+    if let Err(e) = state.inspect(data) {
+        return Err(e.to_string());
+    }
+    let (colortype, bitdepth) = match (state.info_png.color.colortype, state.info_png.color.bitdepth) {
+        (ColorType::LCT_PALETTE, _) => (ColorType::LCT_RGBA, 8),
+        (colortype, bitdepth) => (colortype, if bitdepth < 8 { 8 } else { bitdepth }), // only grey can be < 8 bits
+    };
+
+    let image = match lodepng::decode_memory(data, colortype, bitdepth) {
+        Ok(image) => image,
+        Err(e) => return Err(e.to_string()),
+    };
+    // lodepng leaves 16 bit samples in the PNG's big endian byte order
+    let (pixels, width, height) = match image {
+        lodepng::Image::Grey(bitmap) => (Pixels::Grey(to_vec(&bitmap)), bitmap.width, bitmap.height),
+        lodepng::Image::Grey16(bitmap) =>
+            (Pixels::Grey16(map(&bitmap, grey16_byte_order)), bitmap.width, bitmap.height),
+        lodepng::Image::GreyAlpha(bitmap) => (Pixels::GreyAlpha(to_vec(&bitmap)), bitmap.width, bitmap.height),
+        lodepng::Image::GreyAlpha16(bitmap) =>
+            (Pixels::GreyAlpha16(map(&bitmap, grey_alpha16_byte_order)), bitmap.width, bitmap.height),
+        lodepng::Image::RGB(bitmap) => (Pixels::Rgb(to_vec(&bitmap)), bitmap.width, bitmap.height),
+        lodepng::Image::RGB16(bitmap) => (Pixels::Rgb16(map(&bitmap, rgb16_byte_order)), bitmap.width, bitmap.height),
+        lodepng::Image::RGBA(bitmap) => (Pixels::Rgba(to_vec(&bitmap)), bitmap.width, bitmap.height),
+        lodepng::Image::RGBA16(bitmap) =>
+            (Pixels::Rgba16(map(&bitmap, rgba16_byte_order)), bitmap.width, bitmap.height),
+        _ => return Err(format!("unsupported colour type {:?} with bit depth {}", colortype, bitdepth)),
+    };
+    Ok(Picture { pixels: pixels, width: width, height: height })
+}
+
+fn save_png(path: &Path, picture: &Picture) -> Result<(), String> {
+    let (width, height) = (picture.width, picture.height);
+    let result = match picture.pixels {
+        Pixels::Grey(ref pixels) => lodepng::encode_file(path, pixels, width, height, ColorType::LCT_GREY, 8),
+        Pixels::Grey16(ref pixels) => lodepng::encode_file(path, &pixels.iter().map(grey16_byte_order)
+            .collect::<Vec<_>>(), width, height, ColorType::LCT_GREY, 16),
+        Pixels::GreyAlpha(ref pixels) =>
+            lodepng::encode_file(path, pixels, width, height, ColorType::LCT_GREY_ALPHA, 8),
+        Pixels::GreyAlpha16(ref pixels) => lodepng::encode_file(path, &pixels.iter().map(grey_alpha16_byte_order)
+            .collect::<Vec<_>>(), width, height, ColorType::LCT_GREY_ALPHA, 16),
+        Pixels::Rgb(ref pixels) => lodepng::encode_file(path, pixels, width, height, ColorType::LCT_RGB, 8),
+        Pixels::Rgb16(ref pixels) => lodepng::encode_file(path, &pixels.iter().map(rgb16_byte_order)
+            .collect::<Vec<_>>(), width, height, ColorType::LCT_RGB, 16),
+        Pixels::Rgba(ref pixels) => lodepng::encode_file(path, pixels, width, height, ColorType::LCT_RGBA, 8),
+        Pixels::Rgba16(ref pixels) => lodepng::encode_file(path, &pixels.iter().map(rgba16_byte_order)
+            .collect::<Vec<_>>(), width, height, ColorType::LCT_RGBA, 16),
+    };
+    result.map_err(|e| e.to_string())
+}
+
+fn rgb8<P: Pixel>(pixels: &[P]) -> Vec<RGB<u8>> {
+    pixels.iter().map(|pixel| pixel.to_rgb8()).collect()
+}
+
+fn to_vec<P: Copy>(bitmap: &Bitmap<P>) -> Vec<P> {
+    bitmap.buffer.as_ref().to_vec()
+}
+
+fn map<P: Copy>(bitmap: &Bitmap<P>, f: fn(&P) -> P) -> Vec<P> {
+    bitmap.buffer.as_ref().iter().map(f).collect()
+}
+
+// converting between big endian and native byte order is the same swap (or lack of one) in either direction
+
+fn grey16_byte_order(p: &Grey<u16>) -> Grey<u16> {
+    Grey(u16::from_be(p.0))
+}
+
+fn grey_alpha16_byte_order(p: &GreyAlpha<u16>) -> GreyAlpha<u16> {
+    GreyAlpha(u16::from_be(p.0), u16::from_be(p.1))
+}
+
+fn rgb16_byte_order(p: &RGB<u16>) -> RGB<u16> {
+    RGB { r: u16::from_be(p.r), g: u16::from_be(p.g), b: u16::from_be(p.b) }
+}
+
+fn rgba16_byte_order(p: &RGBA<u16>) -> RGBA<u16> {
+    RGBA { r: u16::from_be(p.r), g: u16::from_be(p.g), b: u16::from_be(p.b), a: u16::from_be(p.a) }
+}
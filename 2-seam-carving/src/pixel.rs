@@ -0,0 +1,368 @@
+use lodepng::{Grey, GreyAlpha, RGB, RGBA};
+
+/// A pixel of any format lodepng can decode to, so images can be carved without converting them to 8 bit RGB (which
+/// would lose transparency and precision).
+pub trait Pixel: Copy + Send + Sync {
+    /// The colour used to calculate energy: 8 bits per channel, with any transparency blended against black so the
+    /// edges of transparent areas count as features.
+    fn to_rgb8(&self) -> RGB<u8>;
+
+    /// A pixel halfway between this one and `other` (including transparency), used when inserting seams.
+    fn average(&self, other: &Self) -> Self;
+
+    /// An opaque pixel as close to the given colour as the format allows, used for previews and energy displays.
+    fn from_rgb8(colour: RGB<u8>) -> Self;
+
+    /// A name for the pixel format, e.g. "rgba16", so files of pixels can say which format they hold.
+    fn format_name() -> &'static str;
+
+    /// How many channels `push_channels` pushes.
+    fn channel_count() -> usize;
+
+    /// Adds the pixel's channels (widened to 16 bits, so every format can be saved the same way) to `channels`.
+    fn push_channels(&self, channels: &mut Vec<u16>);
+
+    /// The pixel with the given channels, as pushed by `push_channels`.
+    fn from_channels(channels: &[u16]) -> Self;
+}
+
+fn mean8(a: u8, b: u8) -> u8 {
+    ((a as u16 + b as u16) / 2) as u8
+}
+
+fn mean16(a: u16, b: u16) -> u16 {
+    ((a as u32 + b as u32) / 2) as u16
+}
+
+fn narrow(v: u16) -> u8 {
+    (v >> 8) as u8
+}
+
+fn widen(v: u8) -> u16 {
+    v as u16 * 257 // so 255 becomes 65535
+}
+
+fn blend(v: u8, alpha: u8) -> u8 {
+    (v as u16 * alpha as u16 / 255) as u8
+}
+
+fn luma(colour: RGB<u8>) -> u8 {
+    ((colour.r as u16 + colour.g as u16 + colour.b as u16) / 3) as u8
+}
+
+impl Pixel for RGB<u8> {
+    fn to_rgb8(&self) -> RGB<u8> {
+        *self
+    }
+
+    fn average(&self, other: &RGB<u8>) -> RGB<u8> {
+        RGB { r: mean8(self.r, other.r), g: mean8(self.g, other.g), b: mean8(self.b, other.b) }
+    }
+
+    fn from_rgb8(colour: RGB<u8>) -> RGB<u8> {
+        colour
+    }
+
+    fn format_name() -> &'static str {
+        "rgb8"
+    }
+
+    fn channel_count() -> usize {
+        3
+    }
+
+    fn push_channels(&self, channels: &mut Vec<u16>) {
+        channels.push(self.r as u16);
+        channels.push(self.g as u16);
+        channels.push(self.b as u16);
+    }
+
+    fn from_channels(channels: &[u16]) -> RGB<u8> {
+        RGB { r: channels[0] as u8, g: channels[1] as u8, b: channels[2] as u8 }
+    }
+}
+
+impl Pixel for RGB<u16> {
+    fn to_rgb8(&self) -> RGB<u8> {
+        RGB { r: narrow(self.r), g: narrow(self.g), b: narrow(self.b) }
+    }
+
+    fn average(&self, other: &RGB<u16>) -> RGB<u16> {
+        RGB { r: mean16(self.r, other.r), g: mean16(self.g, other.g), b: mean16(self.b, other.b) }
+    }
+
+    fn from_rgb8(colour: RGB<u8>) -> RGB<u16> {
+        RGB { r: widen(colour.r), g: widen(colour.g), b: widen(colour.b) }
+    }
+
+    fn format_name() -> &'static str {
+        "rgb16"
+    }
+
+    fn channel_count() -> usize {
+        3
+    }
+
+    fn push_channels(&self, channels: &mut Vec<u16>) {
+        channels.push(self.r);
+        channels.push(self.g);
+        channels.push(self.b);
+    }
+
+    fn from_channels(channels: &[u16]) -> RGB<u16> {
+        RGB { r: channels[0], g: channels[1], b: channels[2] }
+    }
+}
+
+impl Pixel for RGBA<u8> {
+    fn to_rgb8(&self) -> RGB<u8> {
+        RGB { r: blend(self.r, self.a), g: blend(self.g, self.a), b: blend(self.b, self.a) }
+    }
+
+    fn average(&self, other: &RGBA<u8>) -> RGBA<u8> {
+        RGBA {
+            r: mean8(self.r, other.r),
+            g: mean8(self.g, other.g),
+            b: mean8(self.b, other.b),
+            a: mean8(self.a, other.a),
+        }
+    }
+
+    fn from_rgb8(colour: RGB<u8>) -> RGBA<u8> {
+        RGBA { r: colour.r, g: colour.g, b: colour.b, a: 255 }
+    }
+
+    fn format_name() -> &'static str {
+        "rgba8"
+    }
+
+    fn channel_count() -> usize {
+        4
+    }
+
+    fn push_channels(&self, channels: &mut Vec<u16>) {
+        channels.push(self.r as u16);
+        channels.push(self.g as u16);
+        channels.push(self.b as u16);
+        channels.push(self.a as u16);
+    }
+
+    fn from_channels(channels: &[u16]) -> RGBA<u8> {
+        RGBA { r: channels[0] as u8, g: channels[1] as u8, b: channels[2] as u8, a: channels[3] as u8 }
+    }
+}
+
+impl Pixel for RGBA<u16> {
+    fn to_rgb8(&self) -> RGB<u8> {
+        let alpha = narrow(self.a);
+        RGB { r: blend(narrow(self.r), alpha), g: blend(narrow(self.g), alpha), b: blend(narrow(self.b), alpha) }
+    }
+
+    fn average(&self, other: &RGBA<u16>) -> RGBA<u16> {
+        RGBA {
+            r: mean16(self.r, other.r),
+            g: mean16(self.g, other.g),
+            b: mean16(self.b, other.b),
+            a: mean16(self.a, other.a),
+        }
+    }
+
+    fn from_rgb8(colour: RGB<u8>) -> RGBA<u16> {
+        RGBA { r: widen(colour.r), g: widen(colour.g), b: widen(colour.b), a: 65535 }
+    }
+
+    fn format_name() -> &'static str {
+        "rgba16"
+    }
+
+    fn channel_count() -> usize {
+        4
+    }
+
+    fn push_channels(&self, channels: &mut Vec<u16>) {
+        channels.push(self.r);
+        channels.push(self.g);
+        channels.push(self.b);
+        channels.push(self.a);
+    }
+
+    fn from_channels(channels: &[u16]) -> RGBA<u16> {
+        RGBA { r: channels[0], g: channels[1], b: channels[2], a: channels[3] }
+    }
+}
+
+impl Pixel for Grey<u8> {
+    fn to_rgb8(&self) -> RGB<u8> {
+        RGB { r: self.0, g: self.0, b: self.0 }
+    }
+
+    fn average(&self, other: &Grey<u8>) -> Grey<u8> {
+        Grey(mean8(self.0, other.0))
+    }
+
+    fn from_rgb8(colour: RGB<u8>) -> Grey<u8> {
+        Grey(luma(colour))
+    }
+
+    fn format_name() -> &'static str {
+        "grey8"
+    }
+
+    fn channel_count() -> usize {
+        1
+    }
+
+    fn push_channels(&self, channels: &mut Vec<u16>) {
+        channels.push(self.0 as u16);
+    }
+
+    fn from_channels(channels: &[u16]) -> Grey<u8> {
+        Grey(channels[0] as u8)
+    }
+}
+
+impl Pixel for Grey<u16> {
+    fn to_rgb8(&self) -> RGB<u8> {
+        let v = narrow(self.0);
+        RGB { r: v, g: v, b: v }
+    }
+
+    fn average(&self, other: &Grey<u16>) -> Grey<u16> {
+        Grey(mean16(self.0, other.0))
+    }
+
+    fn from_rgb8(colour: RGB<u8>) -> Grey<u16> {
+        Grey(widen(luma(colour)))
+    }
+
+    fn format_name() -> &'static str {
+        "grey16"
+    }
+
+    fn channel_count() -> usize {
+        1
+    }
+
+    fn push_channels(&self, channels: &mut Vec<u16>) {
+        channels.push(self.0);
+    }
+
+    fn from_channels(channels: &[u16]) -> Grey<u16> {
+        Grey(channels[0])
+    }
+}
+
+impl Pixel for GreyAlpha<u8> {
+    fn to_rgb8(&self) -> RGB<u8> {
+        let v = blend(self.0, self.1);
+        RGB { r: v, g: v, b: v }
+    }
+
+    fn average(&self, other: &GreyAlpha<u8>) -> GreyAlpha<u8> {
+        GreyAlpha(mean8(self.0, other.0), mean8(self.1, other.1))
+    }
+
+    fn from_rgb8(colour: RGB<u8>) -> GreyAlpha<u8> {
+        GreyAlpha(luma(colour), 255)
+    }
+
+    fn format_name() -> &'static str {
+        "grey-alpha8"
+    }
+
+    fn channel_count() -> usize {
+        2
+    }
+
+    fn push_channels(&self, channels: &mut Vec<u16>) {
+        channels.push(self.0 as u16);
+        channels.push(self.1 as u16);
+    }
+
+    fn from_channels(channels: &[u16]) -> GreyAlpha<u8> {
+        GreyAlpha(channels[0] as u8, channels[1] as u8)
+    }
+}
+
+impl Pixel for GreyAlpha<u16> {
+    fn to_rgb8(&self) -> RGB<u8> {
+        let v = blend(narrow(self.0), narrow(self.1));
+        RGB { r: v, g: v, b: v }
+    }
+
+    fn average(&self, other: &GreyAlpha<u16>) -> GreyAlpha<u16> {
+        GreyAlpha(mean16(self.0, other.0), mean16(self.1, other.1))
+    }
+
+    fn from_rgb8(colour: RGB<u8>) -> GreyAlpha<u16> {
+        GreyAlpha(widen(luma(colour)), 65535)
+    }
+
+    fn format_name() -> &'static str {
+        "grey-alpha16"
+    }
+
+    fn channel_count() -> usize {
+        2
+    }
+
+    fn push_channels(&self, channels: &mut Vec<u16>) {
+        channels.push(self.0);
+        channels.push(self.1);
+    }
+
+    fn from_channels(channels: &[u16]) -> GreyAlpha<u16> {
+        GreyAlpha(channels[0], channels[1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lodepng::{Grey, GreyAlpha, RGB, RGBA};
+    use super::Pixel;
+
+    #[test]
+    fn blends_transparent_pixels_against_black_for_energy() {
+        assert_eq!(RGBA { r: 200u8, g: 100, b: 50, a: 255 }.to_rgb8(), RGB { r: 200, g: 100, b: 50 });
+        assert_eq!(RGBA { r: 200u8, g: 100, b: 50, a: 0 }.to_rgb8(), RGB { r: 0, g: 0, b: 0 });
+        assert_eq!(GreyAlpha(200u8, 51).to_rgb8(), RGB { r: 40, g: 40, b: 40 });
+    }
+
+    #[test]
+    fn scales_16_bit_pixels_to_8_bits_for_energy() {
+        assert_eq!(RGB { r: 65535u16, g: 32768, b: 255 }.to_rgb8(), RGB { r: 255, g: 128, b: 0 });
+        assert_eq!(Grey(65535u16).to_rgb8(), RGB { r: 255, g: 255, b: 255 });
+    }
+
+    #[test]
+    fn averages_every_channel_including_alpha() {
+        let a = RGBA { r: 0u16, g: 100, b: 65535, a: 0 };
+        let b = RGBA { r: 10u16, g: 300, b: 65535, a: 65535 };
+
+        assert_eq!(a.average(&b), RGBA { r: 5, g: 200, b: 65535, a: 32767 });
+    }
+
+    #[test]
+    fn round_trips_pixels_through_their_channels() {
+        fn round_trip<P: Pixel + PartialEq + ::std::fmt::Debug>(pixel: P) {
+            let mut channels = Vec::new();
+            pixel.push_channels(&mut channels);
+            assert_eq!(channels.len(), P::channel_count());
+            assert_eq!(P::from_channels(&channels), pixel);
+        }
+
+        round_trip(RGB { r: 1u8, g: 2, b: 255 });
+        round_trip(RGBA { r: 1u16, g: 2, b: 3, a: 65535 });
+        round_trip(Grey(200u8));
+        round_trip(GreyAlpha(60000u16, 7));
+    }
+
+    #[test]
+    fn creates_opaque_pixels_from_rgb() {
+        let red = RGB { r: 255, g: 0, b: 0 };
+
+        assert_eq!(RGBA::<u8>::from_rgb8(red), RGBA { r: 255, g: 0, b: 0, a: 255 });
+        assert_eq!(RGB::<u16>::from_rgb8(red), RGB { r: 65535, g: 0, b: 0 });
+        assert_eq!(GreyAlpha::<u8>::from_rgb8(red), GreyAlpha(85, 255));
+    }
+}
@@ -0,0 +1,182 @@
+use lodepng::{Grey, RGB};
+use picture::{Picture, Pixels};
+use std::str;
+
+/// Decodes a binary (P5/P6) or plain text (P2/P3) PGM or PPM image. Samples are scaled up to 8 bits, or to 16 bits if
+/// the image's maximum value needs more than 8.
+pub fn decode(data: &[u8]) -> Result<Picture, String> {
+    let mut header = Header { data: data, position: 0 };
+    let (channels, binary) = match try!(header.token()) {
+        "P2" => (1, false),
+        "P3" => (3, false),
+        "P5" => (1, true),
+        "P6" => (3, true),
+        magic => return Err(format!("unsupported PNM type {}", magic)),
+    };
+    let width = try!(header.number());
+    let height = try!(header.number());
+    let max_value = try!(header.number());
+    if width == 0 || height == 0 {
+        return Err(format!("invalid dimensions {} x {}", width, height));
+    }
+    if max_value == 0 || max_value > 65535 {
+        return Err(format!("invalid maximum value {}", max_value));
+    }
+    let wide = max_value > 255;
+
+    let count = try!(width.checked_mul(height).and_then(|pixels| pixels.checked_mul(channels))
+        .ok_or(format!("image is too big at {} x {}", width, height)));
+    // the header can claim any size, so check there's enough data for it before making room for the samples; exactly
+    // 1 whitespace character separates the header from binary samples, and plain text samples are at least a space
+    // and a digit
+    let sample_size = if binary && !wide { 1 } else { 2 };
+    let start = if binary { header.position + 1 } else { header.position };
+    match count.checked_mul(sample_size).and_then(|size| size.checked_add(start)) {
+        Some(end) if end <= data.len() => (),
+        _ => return Err("image data is truncated".to_owned()),
+    }
+    let mut samples = Vec::with_capacity(count);
+    if binary {
+        for i in 0..count {
+            let at = start + i * sample_size;
+            samples.push(if wide { (data[at] as usize) << 8 | data[at + 1] as usize } else { data[at] as usize });
+        }
+    } else {
+        for _ in 0..count {
+            samples.push(try!(header.number()));
+        }
+    }
+
+    let full = if wide { 65535 } else { 255 };
+    if let Some(&sample) = samples.iter().find(|&&sample| sample > max_value) {
+        return Err(format!("sample {} is more than the maximum value {}", sample, max_value));
+    }
+    let scale = |sample: usize| sample * full / max_value;
+    let pixels = match (channels, wide) {
+        (1, false) => Pixels::Grey(samples.iter().map(|&v| Grey(scale(v) as u8)).collect()),
+        (1, true) => Pixels::Grey16(samples.iter().map(|&v| Grey(scale(v) as u16)).collect()),
+        (_, false) => Pixels::Rgb(samples.chunks(3)
+            .map(|c| RGB { r: scale(c[0]) as u8, g: scale(c[1]) as u8, b: scale(c[2]) as u8 }).collect()),
+        (_, true) => Pixels::Rgb16(samples.chunks(3)
+            .map(|c| RGB { r: scale(c[0]) as u16, g: scale(c[1]) as u16, b: scale(c[2]) as u16 }).collect()),
+    };
+    Ok(Picture { pixels: pixels, width: width, height: height })
+}
+
+/// Encodes the picture as a binary PGM (if it's grey) or PPM, keeping its bit depth. PNM has no transparency, so any
+/// alpha channel is dropped.
+pub fn encode(picture: &Picture) -> Vec<u8> {
+    let (magic, wide, samples): (&str, bool, Vec<u16>) = match picture.pixels {
+        Pixels::Grey(ref p) => ("P5", false, p.iter().map(|p| p.0 as u16).collect()),
+        Pixels::Grey16(ref p) => ("P5", true, p.iter().map(|p| p.0).collect()),
+        Pixels::GreyAlpha(ref p) => ("P5", false, p.iter().map(|p| p.0 as u16).collect()),
+        Pixels::GreyAlpha16(ref p) => ("P5", true, p.iter().map(|p| p.0).collect()),
+        Pixels::Rgb(ref p) => ("P6", false, p.iter().flat_map(|p| vec!(p.r as u16, p.g as u16, p.b as u16)).collect()),
+        Pixels::Rgb16(ref p) => ("P6", true, p.iter().flat_map(|p| vec!(p.r, p.g, p.b)).collect()),
+        Pixels::Rgba(ref p) => ("P6", false, p.iter().flat_map(|p| vec!(p.r as u16, p.g as u16, p.b as u16)).collect()),
+        Pixels::Rgba16(ref p) => ("P6", true, p.iter().flat_map(|p| vec!(p.r, p.g, p.b)).collect()),
+    };
+
+    let mut data = format!("{}\n{} {}\n{}\n", magic, picture.width, picture.height, if wide { 65535 } else { 255 })
+        .into_bytes();
+    for sample in samples {
+        if wide {
+            data.push((sample >> 8) as u8);
+        }
+        data.push(sample as u8);
+    }
+    data
+}
+
+/// Reads the whitespace separated parts of a PNM header (or of a plain text image's samples), skipping comments.
+struct Header<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Header<'a> {
+    fn token(&mut self) -> Result<&'a str, String> {
+        loop {
+            match self.data.get(self.position) {
+                Some(&b'#') => while self.position < self.data.len() && self.data[self.position] != b'\n' {
+                    self.position += 1;
+                },
+                Some(c) if (*c as char).is_whitespace() => self.position += 1,
+                Some(_) => break,
+                None => return Err("unexpected end of header".to_owned()),
+            }
+        }
+        let start = self.position;
+        while self.position < self.data.len() && !(self.data[self.position] as char).is_whitespace() {
+            self.position += 1;
+        }
+        str::from_utf8(&self.data[start..self.position]).map_err(|e| e.to_string())
+    }
+
+    fn number(&mut self) -> Result<usize, String> {
+        let token = try!(self.token());
+        token.parse().map_err(|_| format!("expected a number but found {}", token))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lodepng::{Grey, RGB, RGBA};
+    use picture::{Picture, Pixels};
+    use super::{decode, encode};
+
+    fn pixels_of(picture: &Picture) -> (usize, usize, Vec<RGB<u16>>) {
+        let pixels = match picture.pixels {
+            Pixels::Rgb16(ref pixels) => pixels.clone(),
+            _ => panic!("expected 16 bit RGB"),
+        };
+        (picture.width, picture.height, pixels)
+    }
+
+    #[test]
+    fn decodes_plain_text_images_with_comments() {
+        let picture = decode(b"P2\n# a comment\n3 1 # width and height\n4\n0 2 4\n").unwrap();
+
+        assert_eq!((picture.width, picture.height), (3, 1));
+        match picture.pixels {
+            Pixels::Grey(pixels) => assert_eq!(pixels, vec!(Grey(0), Grey(127), Grey(255))),
+            _ => panic!("expected 8 bit grey"),
+        }
+    }
+
+    #[test]
+    fn round_trips_16_bit_binary_images() {
+        let original = Picture {
+            pixels: Pixels::Rgb16(vec!(RGB { r: 1, g: 256, b: 65535 }, RGB { r: 300, g: 0, b: 4000 })),
+            width: 1,
+            height: 2,
+        };
+
+        let data = encode(&original);
+
+        assert!(data.starts_with(b"P6\n1 2\n65535\n"));
+        assert_eq!(pixels_of(&decode(&data).unwrap()), pixels_of(&original));
+    }
+
+    #[test]
+    fn drops_transparency_when_encoding() {
+        let picture = Picture { pixels: Pixels::Rgba(vec!(RGBA { r: 1, g: 2, b: 3, a: 4 })), width: 1, height: 1 };
+
+        assert_eq!(encode(&picture), b"P6\n1 1\n255\n\x01\x02\x03".to_vec());
+    }
+
+    #[test]
+    fn rejects_truncated_images() {
+        assert!(decode(b"P5\n2 2\n255\n\x00\x00\x00").is_err());
+        assert!(decode(b"P3\n1 1\n255\n0 0").is_err());
+    }
+
+    #[test]
+    fn rejects_headers_claiming_impossible_sizes() {
+        assert!(decode(b"P2\n100000 100000\n255\n0 0 0\n").is_err());
+        assert!(decode(b"P5\n18446744073709551615 2\n255\n\x00\x00").is_err());
+        assert!(decode(b"P6\n4294967296 4294967296\n255\n\x00").is_err());
+        assert!(decode(b"P5\n0 0\n255\n").is_err());
+        assert!(decode(b"P2\n0 3\n255\n").is_err());
+    }
+}
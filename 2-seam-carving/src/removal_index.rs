@@ -0,0 +1,103 @@
+use carving::{Carver, CarverOptions, Orientation};
+use pixel::Pixel;
+use {lazy_remove_seam, remove_seam, Error, Image};
+
+/// When each pixel of an image would be removed, if its lowest cost vertical seams were removed one after the other
+/// until only 1 column was left. Built once per image, it narrows the image to any width in linear time, without
+/// searching for seams again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemovalIndex {
+    pub order: Vec<usize>, // how many seams are removed before each pixel's seam (the last column left gets width - 1)
+    pub width: usize,
+    pub height: usize,
+}
+
+impl RemovalIndex {
+    /// Finds the order by removing every seam but the last, which takes as long as narrowing the image to 1 pixel. An
+    /// empty image has an empty order.
+    pub fn build<P: Pixel>(image: &Image<P>, options: CarverOptions) -> RemovalIndex {
+        if image.pixels.is_empty() {
+            return RemovalIndex { order: Vec::new(), width: image.width, height: image.height };
+        }
+        let mut order = vec![image.width - 1; image.pixels.len()];
+        let mut remaining = image.clone();
+        // the index into `image` of each remaining pixel
+        let mut original_index = (0..image.pixels.len()).collect::<Vec<_>>();
+        let mut carver = Carver::with_options(image.pixels.len(), options);
+        carver.calculate_energy(image.width, image.height, &image.pixels);
+        for step in 0..(image.width - 1) {
+            let seam = carver.find_oriented_seam(remaining.width, remaining.height, Orientation::Vertical);
+            for &pixel in &seam {
+                order[original_index[pixel]] = step;
+            }
+
+            lazy_remove_seam(&mut original_index[..], remaining.width, remaining.height, Orientation::Vertical, &seam);
+            remove_seam(&mut carver, &mut remaining, Orientation::Vertical, &seam);
+            original_index.truncate(remaining.pixels.len());
+        }
+        RemovalIndex { order: order, width: image.width, height: image.height }
+    }
+
+    /// The image (which the index must have been built from) narrowed to `width`, by keeping only the pixels which
+    /// wouldn't have been removed yet. The image can only be narrowed, to at least 1 pixel.
+    pub fn render_at_width<P: Pixel>(&self, image: &Image<P>, width: usize) -> Result<Image<P>, Error> {
+        assert!(image.width == self.width && image.height == self.height, "index must be of the same size image");
+        if width == 0 || width > self.width {
+            return Err(Error::InvalidSize { width: self.width, height: self.height, new_width: width,
+                new_height: self.height });
+        }
+        let removed = self.width - width;
+        // every seam has exactly 1 pixel per row, so each row keeps `width` pixels
+        let pixels = image.pixels.iter().zip(self.order.iter())
+            .filter(|&(_, &order)| order >= removed)
+            .map(|(&pixel, _)| pixel)
+            .collect();
+        Ok(Image::new(pixels, width, self.height))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use carving::CarverOptions;
+    use lodepng::RGB;
+    use super::RemovalIndex;
+    use {noisy_image, retarget, Image};
+
+    fn image() -> Image<RGB<u8>> {
+        Image::new(noisy_image(7, 6), 7, 6)
+    }
+
+    #[test]
+    fn orders_each_row_by_when_its_pixels_are_removed() {
+        let index = RemovalIndex::build(&image(), CarverOptions::default());
+
+        for row in index.order.chunks(7) {
+            let mut sorted = row.to_vec();
+            sorted.sort();
+            assert_eq!(sorted, (0..7).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn renders_the_same_image_as_removing_seams_at_every_width() {
+        let image = image();
+        let index = RemovalIndex::build(&image, CarverOptions::default());
+
+        assert_eq!(index.render_at_width(&image, 7), Ok(image.clone()));
+        for width in 1..7 {
+            assert_eq!(index.render_at_width(&image, width), retarget(&image, width, 6, CarverOptions::default()));
+        }
+        assert!(index.render_at_width(&image, 0).is_err());
+        assert!(index.render_at_width(&image, 8).is_err());
+    }
+
+    #[test]
+    fn indexes_empty_images() {
+        let empty = Image::new(Vec::<RGB<u8>>::new(), 0, 3);
+
+        let index = RemovalIndex::build(&empty, CarverOptions::default());
+
+        assert_eq!(index.order, vec!());
+        assert!(index.render_at_width(&empty, 1).is_err());
+    }
+}
@@ -0,0 +1,98 @@
+use carving::{Carver, CarverOptions, Orientation};
+use pixel::Pixel;
+use {breadth_of, remove_seam, Error, Image};
+
+/// How much seams in each frame cost by default for each pixel they stray from the matching seam in the previous frame
+/// (in each row or column), about a twentieth of the energy of the sharpest edge.
+pub const DEFAULT_COHERENCE: i32 = 10000;
+
+/// Shrinks every frame of a video (which must all be the same size) to `new_width` x `new_height`, removing vertical
+/// seams and then horizontal ones like `retarget`. Carving each frame separately makes seams jump around between
+/// similar frames, so the video flickers; instead each seam costs `coherence` more for each pixel it strays from the
+/// seam removed at the same point of the previous frame, so seams only move when the content does.
+///
+/// Frames can only be shrunk, to at least 1 x 1. Panics if the frames aren't all the same size.
+pub fn retarget_frames<P: Pixel>(frames: &[Image<P>], new_width: usize, new_height: usize, options: CarverOptions,
+        coherence: i32) -> Result<Vec<Image<P>>, Error> {
+    let mut previous_seams: Vec<Vec<usize>> = Vec::new(); // the offsets of each seam removed from the previous frame
+    let mut retargeted = Vec::with_capacity(frames.len());
+    for frame in frames {
+        assert!(frame.width == frames[0].width && frame.height == frames[0].height, "frames must be the same size");
+        if new_width == 0 || new_height == 0 || new_width > frame.width || new_height > frame.height {
+            return Err(Error::InvalidSize { width: frame.width, height: frame.height, new_width: new_width,
+                new_height: new_height });
+        }
+
+        let mut image = frame.clone();
+        let mut carver = Carver::with_options(image.pixels.len(), options);
+        carver.calculate_energy(image.width, image.height, &image.pixels);
+        let mut seams = Vec::new();
+        let targets = [(Orientation::Vertical, new_width), (Orientation::Horizontal, new_height)];
+        for &(orientation, target) in targets.iter() {
+            while breadth_of(&image, orientation) > target {
+                carver.set_guide(previous_seams.get(seams.len()).cloned().unwrap_or(Vec::new()), coherence);
+                let seam = carver.find_oriented_seam(image.width, image.height, orientation);
+                let width = image.width;
+                seams.push(seam.iter().map(|&pixel| match orientation {
+                    Orientation::Vertical => pixel % width,
+                    Orientation::Horizontal => pixel / width,
+                }).collect());
+                remove_seam(&mut carver, &mut image, orientation, &seam);
+            }
+        }
+        previous_seams = seams;
+        retargeted.push(image);
+    }
+    Ok(retargeted)
+}
+
+#[cfg(test)]
+mod tests {
+    use carving::CarverOptions;
+    use energy::BorderPolicy;
+    use lodepng::Grey;
+    use super::retarget_frames;
+    use {noisy_image, Image};
+
+    /// A noisy frame with a flat stripe 3 pixels wide around each of the given columns, which are the cheapest places
+    /// for seams.
+    fn frame(stripes: &[usize]) -> Image<Grey<u8>> {
+        let pixels = noisy_image(8, 6).iter().enumerate().map(|(i, noise)| {
+            let x = i % 8;
+            let flat = stripes.iter().any(|&stripe| x + 1 >= stripe && x <= stripe + 1);
+            Grey(if flat { 128 } else { noise.r })
+        }).collect();
+        Image::new(pixels, 8, 6)
+    }
+
+    fn without_column(image: &Image<Grey<u8>>, column: usize) -> Image<Grey<u8>> {
+        let pixels = image.pixels.iter().enumerate().filter(|&(i, _)| i % image.width != column).map(|(_, &p)| p);
+        Image::new(pixels.collect(), image.width - 1, image.height)
+    }
+
+    #[test]
+    fn shrinks_every_frame() {
+        let frames = vec!(frame(&[2]), frame(&[2]), frame(&[5]));
+
+        let retargeted = retarget_frames(&frames, 6, 5, CarverOptions::default(), 0).unwrap();
+
+        assert_eq!(retargeted.len(), 3);
+        assert!(retargeted.iter().all(|frame| frame.width == 6 && frame.height == 5));
+        assert!(retarget_frames(&frames, 9, 5, CarverOptions::default(), 0).is_err());
+    }
+
+    #[test]
+    fn keeps_seams_where_they_were_in_the_previous_frame() {
+        // the stripe moves between 2 columns; carved separately, the seam would follow it (the border is mirrored so
+        // seams through the first and last rows aren't all equally expensive)
+        let frames = vec!(frame(&[2]), frame(&[2, 5]), frame(&[5]));
+        let options = CarverOptions { border: BorderPolicy::Mirrored, ..CarverOptions::default() };
+
+        let separate = retarget_frames(&frames, 7, 6, options, 0).unwrap();
+        let coherent = retarget_frames(&frames, 7, 6, options, 1 << 20).unwrap();
+
+        assert_eq!(coherent[0], without_column(&frames[0], 2));
+        assert_eq!(separate[2], without_column(&frames[2], 5));
+        assert_eq!(coherent[2], without_column(&frames[2], 2));
+    }
+}
@@ -0,0 +1,187 @@
+use lodepng::RGB;
+use std::cmp;
+use std::io::{self, Write};
+
+/// How values are spread over a colour map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scaling {
+    /// In proportion to where they are between the smallest and largest value.
+    Linear,
+    /// Like `Linear`, but on a log scale, so differences between small values are visible next to large ones.
+    Log,
+    /// By how many values are smaller, so each colour is used about as often as any other.
+    Histogram,
+}
+
+impl Scaling {
+    pub fn from_name(name: &str) -> Option<Scaling> {
+        match name {
+            "linear" => Some(Scaling::Linear),
+            "log" => Some(Scaling::Log),
+            "histogram" => Some(Scaling::Histogram),
+            _ => None,
+        }
+    }
+}
+
+/// The colours values are shown as, from the smallest to the largest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColourMap {
+    Grey, // black to white
+    Viridis, // purple to yellow, evenly bright steps which are still distinct to colour blind viewers
+    Heat, // black through red and yellow to white
+}
+
+const GREY: [(u8, u8, u8); 2] = [(0, 0, 0), (255, 255, 255)];
+const VIRIDIS: [(u8, u8, u8); 9] = [(68, 1, 84), (71, 44, 122), (59, 81, 139), (44, 113, 142), (33, 144, 141),
+    (39, 173, 129), (92, 200, 99), (170, 220, 50), (253, 231, 37)];
+const HEAT: [(u8, u8, u8); 4] = [(0, 0, 0), (255, 0, 0), (255, 255, 0), (255, 255, 255)];
+
+impl ColourMap {
+    pub fn from_name(name: &str) -> Option<ColourMap> {
+        match name {
+            "grey" | "gray" => Some(ColourMap::Grey),
+            "viridis" => Some(ColourMap::Viridis),
+            "heat" => Some(ColourMap::Heat),
+            _ => None,
+        }
+    }
+
+    /// The colour of a value scaled to between 0 and 1, interpolated between the map's evenly spaced stops.
+    pub fn colour(&self, t: f64) -> RGB<u8> {
+        let stops: &[(u8, u8, u8)] = match *self {
+            ColourMap::Grey => &GREY,
+            ColourMap::Viridis => &VIRIDIS,
+            ColourMap::Heat => &HEAT,
+        };
+        let position = t.max(0.0).min(1.0) * (stops.len() - 1) as f64;
+        let stop = (position as usize).min(stops.len() - 2);
+        let fraction = position - stop as f64;
+        let (from, to) = (stops[stop], stops[stop + 1]);
+        let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * fraction).round() as u8;
+        RGB { r: mix(from.0, to.0), g: mix(from.1, to.1), b: mix(from.2, to.2) }
+    }
+}
+
+/// How to show values (such as energy) as an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Visualisation {
+    pub scaling: Scaling,
+    pub colour_map: ColourMap,
+}
+
+impl Default for Visualisation {
+    fn default() -> Visualisation {
+        Visualisation { scaling: Scaling::Linear, colour_map: ColourMap::Grey }
+    }
+}
+
+impl Visualisation {
+    /// The colour of each value, scaled between the smallest and largest of them.
+    pub fn colours(&self, values: &[i64]) -> Vec<RGB<u8>> {
+        scale(values, self.scaling).iter().map(|&t| self.colour_map.colour(t)).collect()
+    }
+}
+
+/// Scales each value to between 0 (the smallest value) and 1 (the largest). If they're all the same, they're all 0.
+pub fn scale(values: &[i64], scaling: Scaling) -> Vec<f64> {
+    let (min, max) = match (values.iter().min(), values.iter().max()) {
+        (Some(&min), Some(&max)) if min < max => (min, max),
+        _ => return vec![0.0; values.len()],
+    };
+    // the range can be too big for an i64, e.g. from protected to removed pixels
+    let offset = |v: i64| v as f64 - min as f64;
+    let range = offset(max);
+
+    match scaling {
+        Scaling::Linear => values.iter().map(|&v| offset(v) / range).collect(),
+        Scaling::Log => values.iter().map(|&v| offset(v).ln_1p() / range.ln_1p()).collect(),
+        Scaling::Histogram => {
+            // the fraction of values no bigger than each distinct value, with the smallest value's fraction moved to 0
+            let mut sorted = values.to_vec();
+            sorted.sort();
+            let mut cumulative: Vec<(i64, usize)> = Vec::new();
+            for (i, &v) in sorted.iter().enumerate() {
+                if cumulative.last().map_or(false, |&(last, _)| last == v) {
+                    cumulative.pop();
+                }
+                cumulative.push((v, i + 1));
+            }
+            let fewest = cumulative[0].1 as f64;
+            let fraction = |v: i64| {
+                let index = cumulative.binary_search_by(|&(value, _)| value.cmp(&v)).ok().expect("value was counted");
+                (cumulative[index].1 as f64 - fewest) / (values.len() as f64 - fewest)
+            };
+            values.iter().map(|&v| fraction(v)).collect()
+        },
+    }
+}
+
+/// Writes the values of a `width` pixel wide image as comma separated values, 1 line per row.
+pub fn write_csv<W: Write>(writer: &mut W, values: &[i64], width: usize) -> io::Result<()> {
+    for row in values.chunks(cmp::max(width, 1)) { // an empty image has no rows
+        let line = row.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",");
+        try!(writeln!(writer, "{}", line));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use carving::MAX_PIXEL_ENERGY;
+    use lodepng::RGB;
+    use super::{scale, write_csv, ColourMap, Scaling};
+
+    #[test]
+    fn scales_linearly_without_losing_small_values() {
+        let values = [0, 1000, MAX_PIXEL_ENERGY as i64 / 2, MAX_PIXEL_ENERGY as i64];
+
+        let scaled = scale(&values, Scaling::Linear);
+
+        // integer division would have made everything but the largest 0
+        assert_eq!(scaled[0], 0.0);
+        assert!(scaled[1] > 0.0);
+        assert!((scaled[2] - 0.5).abs() < 1e-4);
+        assert_eq!(scaled[3], 1.0);
+    }
+
+    #[test]
+    fn scales_logarithmically() {
+        let scaled = scale(&[0, 9, 99], Scaling::Log);
+
+        assert_eq!(scaled[0], 0.0);
+        assert!((scaled[1] - 0.5).abs() < 1e-9);
+        assert_eq!(scaled[2], 1.0);
+    }
+
+    #[test]
+    fn equalises_histogram_by_how_many_values_are_smaller() {
+        // most values are tiny, so linear scaling would make them all look the same, but equalisation spreads them out
+        let scaled = scale(&[1, 2, 2, 3, 1000], Scaling::Histogram);
+
+        assert_eq!(scaled, vec!(0.0, 0.5, 0.5, 0.75, 1.0));
+    }
+
+    #[test]
+    fn scales_identical_and_extreme_values() {
+        assert_eq!(scale(&[5, 5], Scaling::Histogram), vec!(0.0, 0.0));
+        assert_eq!(scale(&[i64::min_value(), 0, i64::max_value()], Scaling::Linear), vec!(0.0, 0.5, 1.0));
+    }
+
+    #[test]
+    fn interpolates_between_colour_map_stops() {
+        assert_eq!(ColourMap::Grey.colour(0.5), RGB { r: 128, g: 128, b: 128 });
+        assert_eq!(ColourMap::Heat.colour(0.5), RGB { r: 255, g: 128, b: 0 });
+        assert_eq!(ColourMap::Viridis.colour(0.0), RGB { r: 68, g: 1, b: 84 });
+        assert_eq!(ColourMap::Viridis.colour(1.0), RGB { r: 253, g: 231, b: 37 });
+    }
+
+    #[test]
+    fn writes_one_csv_line_per_row() {
+        let mut csv = Vec::new();
+
+        write_csv(&mut csv, &[1, 2, 3, -4, 5, 6], 3).unwrap();
+
+        assert_eq!(String::from_utf8(csv).unwrap(), "1,2,3\n-4,5,6\n");
+    }
+}
@@ -1,5 +1,5 @@
-use flow_network::{FlowEdge, FlowNetwork};
-use ford_fulkerson;
+use graph::flow_network::{FlowEdge, FlowNetwork};
+use graph::ford_fulkerson;
 use std::f64;
 use std::fmt;
 
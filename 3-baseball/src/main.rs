@@ -1,10 +1,10 @@
+extern crate graph;
+
 use baseball::{BaseballDivision, BaseballTeam};
 use std::env;
 use std::io;
 
 mod baseball;
-mod flow_network;
-mod ford_fulkerson;
 
 #[cfg_attr(test, allow(dead_code))]
 fn main() {
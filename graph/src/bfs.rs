@@ -0,0 +1,125 @@
+use std::collections::VecDeque;
+use {Graph, Neighbour};
+
+/// Breadth first search over anything implementing `Graph`.
+pub struct BfsResult {
+    marked: Vec<bool>,
+    dist_to: Vec<i32>,
+    edge_to: Vec<Option<(usize, usize)>>, // (vertex the edge leaves from, Neighbour::edge of the edge)
+}
+
+/// Searches from all `sources` at once, following every edge.
+pub fn search<G: Graph>(g: &G, sources: Vec<usize>) -> BfsResult {
+    search_filtered(g, sources, None, |_| true)
+}
+
+/// Searches from all `sources` at once, only following edges for which `traversable` returns true, and stopping as soon
+/// as `target` (if given) has been reached.
+pub fn search_filtered<G, F>(g: &G, sources: Vec<usize>, target: Option<usize>, traversable: F) -> BfsResult
+        where G: Graph, F: Fn(&Neighbour) -> bool {
+    let num_vertices = g.vertices();
+    let mut result = BfsResult {
+        marked: vec![false; num_vertices],
+        dist_to: vec![0; num_vertices],
+        edge_to: vec![None; num_vertices],
+    };
+
+    let mut q = VecDeque::new();
+    for v in sources {
+        let v = g.checked_vertex(v);
+        result.marked[v] = true;
+        result.dist_to[v] = 0;
+        q.push_back(v);
+    }
+    while !q.is_empty() && !target.map_or(false, |t| result.marked[t]) {
+        let v = q.pop_front().expect("queue length known to be > 0");
+        g.for_each_neighbour(v, |n| {
+            if !result.marked[n.vertex] && traversable(&n) {
+                result.dist_to[n.vertex] = result.dist_to[v] + 1;
+                result.edge_to[n.vertex] = Some((v, n.edge));
+                result.marked[n.vertex] = true;
+                q.push_back(n.vertex);
+            }
+        });
+    }
+
+    result
+}
+
+impl BfsResult {
+    pub fn has_path_to(&self, v: usize) -> bool {
+        self.marked[v]
+    }
+
+    /// Returns None if there is no path
+    pub fn dist_to(&self, v: usize) -> Option<i32> {
+        if self.marked[v] {
+            Some(self.dist_to[v])
+        } else {
+            None
+        }
+    }
+
+    /// The last edge on the shortest path to `v` as (vertex the edge leaves from, `Neighbour::edge` of the edge), or None
+    /// if `v` is a source or has no path.
+    pub fn edge_to(&self, v: usize) -> Option<(usize, usize)> {
+        self.edge_to[v]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Graph, Neighbour};
+
+    /// Adjacency lists of (vertex, weight) pairs.
+    struct TestGraph(Vec<Vec<(usize, f64)>>);
+
+    impl Graph for TestGraph {
+        fn vertices(&self) -> usize {
+            self.0.len()
+        }
+
+        fn for_each_neighbour<F: FnMut(Neighbour)>(&self, vertex: usize, mut f: F) {
+            for (i, &(w, weight)) in self.0[vertex].iter().enumerate() {
+                f(Neighbour { vertex: w, edge: i, weight: weight });
+            }
+        }
+    }
+
+    #[test]
+    fn records_distances_and_edges_of_shortest_paths() {
+        // Graph: 0 -> 1 -> 2
+        //         \-->----/
+        let g = TestGraph(vec![vec![(1, 1.0), (2, 1.0)], vec![(2, 1.0)], vec![]]);
+
+        let result = search(&g, vec![0]);
+
+        assert_eq!(result.dist_to(2), Some(1));
+        assert_eq!(result.edge_to(2), Some((0, 1)));
+        assert_eq!(result.edge_to(0), None);
+    }
+
+    #[test]
+    fn skips_edges_which_are_not_traversable() {
+        // Graph: 0 -> 1 -> 2, with 0 -> 2 having no weight
+        //         \-->----/
+        let g = TestGraph(vec![vec![(1, 1.0), (2, 0.0)], vec![(2, 1.0)], vec![]]);
+
+        let result = search_filtered(&g, vec![0], None, |n| n.weight > 0.0);
+
+        assert_eq!(result.dist_to(2), Some(2));
+        assert_eq!(result.edge_to(2), Some((1, 0)));
+    }
+
+    #[test]
+    fn stops_once_target_is_reached() {
+        // Graph: 0 -> 1 -> 2
+        let g = TestGraph(vec![vec![(1, 1.0)], vec![(2, 1.0)], vec![]]);
+
+        let result = search_filtered(&g, vec![0], Some(1), |_| true);
+
+        assert!(result.has_path_to(1));
+        assert!(!result.has_path_to(2));
+    }
+}
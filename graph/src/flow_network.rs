@@ -2,6 +2,7 @@ use std::cell::Cell;
 use std::fmt;
 use std::ops::Deref;
 use std::rc::Rc;
+use {Graph, Neighbour};
 
 // based on http://algs4.cs.princeton.edu/64maxflow/FlowEdge.java.html
 #[derive(Clone, PartialEq)]
@@ -86,20 +87,21 @@ impl FlowNetwork {
         self.adj.len()
     }
 
-    fn validate_vertex(&self, vertex: usize) {
-        assert!(vertex <= self.adj.len(), format!("vertex {} is not in the flow network!", vertex));
-    }
-
     pub fn add_edge(&mut self, e: FlowEdge) {
-        self.validate_vertex(e.v);
-        self.validate_vertex(e.w);
+        self.checked_vertex(e.v);
+        self.checked_vertex(e.w);
         let rc_e = Rc::new(e);
         self.adj[rc_e.v].push(rc_e.clone());
         self.adj[rc_e.w].push(rc_e);
     }
 
+    /// Returns the edge which was reported as `Neighbour::edge` == `index` when visiting the neighbours of `vertex`.
+    pub fn edge(&self, vertex: usize, index: usize) -> &FlowEdge {
+        self.adj[self.checked_vertex(vertex)][index].deref()
+    }
+
     pub fn adj(&self, vertex: usize) -> Vec<&FlowEdge> {
-        self.validate_vertex(vertex);
+        self.checked_vertex(vertex);
         // ideally we'd return an iterator instead, but Rust makes it very hard to do that at the moment
         let mut result = Vec::with_capacity(self.adj[vertex].len());
         for rc_e in self.adj[vertex].iter() {
@@ -109,6 +111,21 @@ impl FlowNetwork {
     }
 }
 
+impl Graph for FlowNetwork {
+    fn vertices(&self) -> usize {
+        self.adj.len()
+    }
+
+    /// Visits both the forward and backward edges of `vertex`; the weight of each is its residual capacity towards the
+    /// neighbour, so an edge with a weight of 0 can't be used in an augmenting path.
+    fn for_each_neighbour<F: FnMut(Neighbour)>(&self, vertex: usize, mut f: F) {
+        for (i, e) in self.adj[self.checked_vertex(vertex)].iter().enumerate() {
+            let w = e.other(vertex);
+            f(Neighbour { vertex: w, edge: i, weight: e.residual_capacity_to(w) });
+        }
+    }
+}
+
 impl fmt::Debug for FlowNetwork {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         try!(writeln!(f, "Flow network with {} vertices:", self.adj.len()));
@@ -121,3 +138,23 @@ impl fmt::Debug for FlowNetwork {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{FlowEdge, FlowNetwork};
+    use {Graph, Neighbour};
+
+    #[test]
+    fn neighbours_are_weighted_by_residual_capacity() {
+        let mut g = FlowNetwork::new(2);
+        g.add_edge(FlowEdge::new(0, 1, 5.0, 2.0));
+
+        let mut from_0 = Vec::new();
+        g.for_each_neighbour(0, |n| from_0.push(n));
+        let mut from_1 = Vec::new();
+        g.for_each_neighbour(1, |n| from_1.push(n));
+
+        assert_eq!(from_0, vec![Neighbour { vertex: 1, edge: 0, weight: 3.0 }]);
+        assert_eq!(from_1, vec![Neighbour { vertex: 0, edge: 0, weight: 2.0 }]);
+    }
+}
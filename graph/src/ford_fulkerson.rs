@@ -1,5 +1,5 @@
+use bfs;
 use flow_network::{FlowEdge, FlowNetwork};
-use std::collections::VecDeque;
 use std::f64;
 
 const EPSILON: f64 = 1e-11;
@@ -13,26 +13,12 @@ pub struct FordFulkersonResult<'graph> {
 
 impl<'graph> FordFulkersonResult<'graph> {
     fn has_augmenting_path(&mut self, g: &'graph FlowNetwork, s: usize, t: usize) -> bool {
-        self.marked = vec![false; g.num_vertices()];
-        self.edge_to = vec![None; g.num_vertices()];
-
-        // breadth-first search
-        let mut queue = VecDeque::new();
-        queue.push_back(s);
-        self.marked[s] = true;
-        while !queue.is_empty() && !self.marked[t] {
-            let v = queue.pop_front().expect("queue is known to be non-empty");
-            for e in g.adj(v) {
-                let w = e.other(v);
-                if e.residual_capacity_to(w) > 0.0 {
-                    if !self.marked[w] {
-                        self.edge_to[w] = Some(e);
-                        self.marked[w] = true;
-                        queue.push_back(w);
-                    }
-                }
-            }
-        }
+        // breadth-first search over edges which still have residual capacity
+        let search = bfs::search_filtered(g, vec![s], Some(t), |n| n.weight > 0.0);
+        self.marked = (0..g.num_vertices()).map(|v| search.has_path_to(v)).collect();
+        self.edge_to = (0..g.num_vertices())
+            .map(|v| search.edge_to(v).map(|(from, edge_index)| g.edge(from, edge_index)))
+            .collect();
 
         self.marked[t]
     }
@@ -45,8 +31,8 @@ impl<'graph> FordFulkersonResult<'graph> {
 
 /// Use the Ford-Fulkerson algorithm to find the min-cut/max-flow, updating flow in the graph as we go.
 pub fn calculate(g: &FlowNetwork, s: usize, t: usize) -> FordFulkersonResult {
-    assert!(s <= g.num_vertices(), "source vertex {} is not in flow network of size {}", s, g.num_vertices());
-    assert!(t <= g.num_vertices(), "sink vertex {} is not in flow network of size {}", t, g.num_vertices());
+    assert!(s < g.num_vertices(), "source vertex {} is not in flow network of size {}", s, g.num_vertices());
+    assert!(t < g.num_vertices(), "sink vertex {} is not in flow network of size {}", t, g.num_vertices());
     assert!(s != t, "source and target vertexes are the same! ({})", s);
 
     let mut result = FordFulkersonResult {
@@ -133,3 +119,30 @@ fn excess(g: &FlowNetwork, v: usize) -> f64 {
     excess
 }
 
+#[cfg(test)]
+mod tests {
+    use flow_network::{FlowEdge, FlowNetwork};
+    use super::calculate;
+
+    #[test]
+    fn cuts_the_bottleneck() {
+        let mut g = FlowNetwork::new(4);
+        g.add_edge(FlowEdge::new(0, 1, 5.0, 0.0));
+        g.add_edge(FlowEdge::new(1, 2, 1.0, 0.0));
+        g.add_edge(FlowEdge::new(2, 3, 5.0, 0.0));
+
+        let result = calculate(&g, 0, 3);
+
+        assert_eq!((0..4).map(|v| result.in_cut(v)).collect::<Vec<_>>(), vec![true, true, false, false]);
+        assert_eq!(g.adj(1).iter().map(|e| e.flow()).collect::<Vec<_>>(), vec![1.0, 1.0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "sink vertex 2 is not in flow network of size 2")]
+    fn refuses_a_sink_outside_the_network() {
+        let mut g = FlowNetwork::new(2);
+        g.add_edge(FlowEdge::new(0, 1, 1.0, 0.0));
+
+        calculate(&g, 0, 2);
+    }
+}
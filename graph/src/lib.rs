@@ -0,0 +1,26 @@
+pub mod bfs;
+pub mod flow_network;
+pub mod ford_fulkerson;
+
+/// An edge leading out of a vertex, as seen from that vertex.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Neighbour {
+    pub vertex: usize, // the vertex at the other end of the edge
+    pub edge: usize, // index of the edge among the edges of the vertex it was reached from
+    pub weight: f64,
+}
+
+/// A graph whose vertices are numbered from 0 to `vertices() - 1`.
+pub trait Graph {
+    fn vertices(&self) -> usize;
+
+    /// Calls `f` with each edge leading out of `vertex`, in a consistent order (so `Neighbour::edge` can be used to find
+    /// that edge again later).
+    fn for_each_neighbour<F: FnMut(Neighbour)>(&self, vertex: usize, f: F);
+
+    /// Panics if the given vertex isn't in the graph; returns it otherwise.
+    fn checked_vertex(&self, vertex: usize) -> usize {
+        assert!(vertex < self.vertices(), "Vertex {} must be < num vertices ({})", vertex, self.vertices());
+        vertex
+    }
+}